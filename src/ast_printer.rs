@@ -0,0 +1,195 @@
+use std::slice;
+
+use crate::{
+    expr::{Expr, Visitor as ExprVisitor},
+    literal::Literal,
+    stmt::{Function, Stmt, Visitor as StmtVisitor},
+};
+
+// Prints syntax trees as Lisp-style S-expressions, like the AstPrinter from
+// the book, so `print 1 + 2 * 3;` becomes `(print (+ 1.0 (* 2.0 3.0)))`.
+pub struct AstPrinter;
+
+impl AstPrinter {
+    pub fn new() -> AstPrinter {
+        AstPrinter
+    }
+
+    // Prints each statement on its own line.
+    pub fn print(&mut self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|statement| self.visit_stmt(statement))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn parenthesize(&self, name: &str, parts: &[String]) -> String {
+        let mut output = format!("({name}");
+
+        for part in parts {
+            output.push(' ');
+            output.push_str(part);
+        }
+
+        output.push(')');
+        output
+    }
+
+    fn function(&mut self, function: &Function) -> String {
+        let params: Vec<&str> = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+
+        let mut parts = vec![format!("{}({})", function.name.lexeme, params.join(" "))];
+        for statement in &function.body {
+            parts.push(self.visit_stmt(statement));
+        }
+
+        self.parenthesize("fun", &parts)
+    }
+}
+
+impl Default for AstPrinter {
+    fn default() -> Self {
+        AstPrinter::new()
+    }
+}
+
+impl<'a> ExprVisitor<'a, String> for AstPrinter {
+    fn visit_expr(&mut self, expr: &Expr<'a>) -> String {
+        match expr {
+            Expr::Assign { name, value } => {
+                let value = self.visit_expr(value);
+                self.parenthesize("=", &[name.lexeme.clone(), value])
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let parts = [self.visit_expr(left), self.visit_expr(right)];
+                self.parenthesize(&operator.lexeme, &parts)
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let mut parts = vec![self.visit_expr(callee)];
+                for argument in arguments {
+                    parts.push(self.visit_expr(argument));
+                }
+
+                self.parenthesize("call", &parts)
+            }
+            Expr::Get { object, name } => {
+                let object = self.visit_expr(object);
+                self.parenthesize(".", &[object, name.lexeme.clone()])
+            }
+            Expr::Grouping { expression, .. } => {
+                let expression = self.visit_expr(expression);
+                self.parenthesize("group", &[expression])
+            }
+            Expr::Literal { value, .. } => match value {
+                Literal::String(string) => format!("\"{string}\""),
+                value => value.to_string(),
+            },
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let parts = [
+                    self.visit_expr(object),
+                    name.lexeme.clone(),
+                    self.visit_expr(value),
+                ];
+                self.parenthesize("=", &parts)
+            }
+            Expr::Super { method, .. } => self.parenthesize("super", slice::from_ref(&method.lexeme)),
+            Expr::This(_) => "this".to_string(),
+            Expr::Unary { operator, right } => {
+                let right = self.visit_expr(right);
+                self.parenthesize(&operator.lexeme, &[right])
+            }
+            Expr::Variable(name) => name.lexeme.clone(),
+        }
+    }
+}
+
+impl<'a> StmtVisitor<'a, String> for AstPrinter {
+    fn visit_stmt(&mut self, stmt: &Stmt<'a>) -> String {
+        match stmt {
+            Stmt::Block { statements } => {
+                let parts: Vec<String> = statements
+                    .iter()
+                    .map(|statement| self.visit_stmt(statement))
+                    .collect();
+                self.parenthesize("block", &parts)
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let mut parts = vec![name.lexeme.clone()];
+                if let Some(superclass) = superclass {
+                    parts.push(format!("< {}", self.visit_expr(superclass)));
+                }
+                for method in methods {
+                    parts.push(self.function(method));
+                }
+
+                self.parenthesize("class", &parts)
+            }
+            Stmt::Expression(expression) => {
+                let expression = self.visit_expr(expression);
+                self.parenthesize(";", &[expression])
+            }
+            Stmt::Function(function) => self.function(function),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let mut parts = vec![self.visit_expr(condition), self.visit_stmt(then_branch)];
+
+                match else_branch {
+                    Some(else_branch) => {
+                        parts.push(self.visit_stmt(else_branch));
+                        self.parenthesize("if-else", &parts)
+                    }
+                    None => self.parenthesize("if", &parts),
+                }
+            }
+            Stmt::Print(expression) => {
+                let expression = self.visit_expr(expression);
+                self.parenthesize("print", &[expression])
+            }
+            Stmt::Return { value, .. } => match value {
+                Some(value) => {
+                    let value = self.visit_expr(value);
+                    self.parenthesize("return", &[value])
+                }
+                None => self.parenthesize("return", &[]),
+            },
+            Stmt::Var { name, initializer } => match initializer {
+                Some(initializer) => {
+                    let initializer = self.visit_expr(initializer);
+                    self.parenthesize("var", &[name.lexeme.clone(), "=".to_string(), initializer])
+                }
+                None => self.parenthesize("var", slice::from_ref(&name.lexeme)),
+            },
+            Stmt::While { condition, body } => {
+                let parts = [self.visit_expr(condition), self.visit_stmt(body)];
+                self.parenthesize("while", &parts)
+            }
+        }
+    }
+}
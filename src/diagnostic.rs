@@ -0,0 +1,372 @@
+use std::{
+    fmt::{Display, Write},
+    str::FromStr,
+};
+
+use crate::{
+    error_code::ErrorCode,
+    interpreter::{RuntimeError, StackFrame},
+    span::Span, token::Token,
+    token_type::TokenType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    // Source snippets with the offending code underlined.
+    Rich,
+    // The exact output of jlox, which the test suite expects.
+    Jlox,
+    // One JSON object per line, for editors and CI tools.
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "rich" => Ok(ErrorFormat::Rich),
+            "jlox" => Ok(ErrorFormat::Jlox),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("Unknown error format '{format}'.")),
+        }
+    }
+}
+
+// The stage of running a program that produced a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Scan,
+    Parse,
+    Resolve,
+    // Only produced by the bytecode compiler, for limits of the VM.
+    Compile,
+    Runtime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// An error ready to be shown to the user, independent of how it gets
+// rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub phase: Phase,
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub message: String,
+    pub span: Span,
+    // Short text printed next to the underlined source, such as "at 'foo'".
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    // For runtime errors, the calls that led to the error, innermost first.
+    pub trace: Vec<StackFrame>,
+}
+
+impl Diagnostic {
+    pub fn new<T: Into<String>>(code: ErrorCode, message: T, span: Span) -> Diagnostic {
+        Diagnostic {
+            phase: code.phase(),
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span,
+            label: None,
+            notes: vec![],
+            trace: vec![],
+        }
+    }
+
+    // An error about a specific token, labelled the way jlox words it.
+    pub fn at_token<T: Into<String>>(code: ErrorCode, token: &Token, message: T) -> Diagnostic {
+        let label = if token.r#type == TokenType::Eof {
+            "at end".to_string()
+        } else {
+            format!("at '{}'", token.lexeme)
+        };
+
+        Diagnostic::new(code, message, token.span).with_label(label)
+    }
+
+    pub fn with_label<T: Into<String>>(mut self, label: T) -> Diagnostic {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_notes(mut self, notes: Vec<String>) -> Diagnostic {
+        self.notes = notes;
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // Renders the diagnostic in the given format, ready to be written to
+    // stderr. `source` is the text the span indexes into.
+    pub fn render(&self, format: ErrorFormat, path: &str, source: &str) -> String {
+        match format {
+            ErrorFormat::Rich => self.render_rich(path, source),
+            ErrorFormat::Jlox => self.render_jlox(),
+            ErrorFormat::Json => format!("{}\n", self.render_json(path, source)),
+        }
+    }
+
+    // Renders the diagnostic in the style of rustc, quoting the offending
+    // line of `source` and underlining the span:
+    //
+    //     error[L0004]: Expect ';' after value.
+    //      --> script.lox:1:9
+    //       |
+    //     1 | print 1 2;
+    //       |         ^ at '2'
+    pub fn render_rich(&self, path: &str, source: &str) -> String {
+        let line_number = self.span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let mut output = String::new();
+        writeln!(
+            output,
+            "{}[{}]: {}",
+            self.severity, self.code, self.message
+        ).unwrap();
+        writeln!(
+            output,
+            "{gutter}--> {path}:{}:{}",
+            self.span.line, self.span.column
+        )
+        .unwrap();
+
+        // A span without a location has line 0. An error at the end of source
+        // that ends with a newline is on the empty line after it, which
+        // `lines` leaves out.
+        let line = self.span.line.checked_sub(1).and_then(|index| {
+            source
+                .lines()
+                .nth(index)
+                .or_else(|| (self.span.offset >= source.len()).then_some(""))
+        });
+
+        if let Some(line) = line {
+            // Reuse the whitespace of the source line so tabs keep the caret
+            // aligned.
+            let padding: String = line
+                .chars()
+                .take(self.span.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            // Only underline up to the end of the first line of the span.
+            let underlined = line
+                .chars()
+                .skip(self.span.column.saturating_sub(1))
+                .take(self.span.length)
+                .count()
+                .min(self.underlined_chars(source))
+                .max(1);
+
+            writeln!(output, "{gutter} |").unwrap();
+            let quoted = format!("{line_number} | {line}");
+            writeln!(output, "{}", quoted.trim_end()).unwrap();
+            write!(output, "{gutter} | {padding}{}", "^".repeat(underlined)).unwrap();
+            if let Some(label) = &self.label {
+                write!(output, " {label}").unwrap();
+            }
+            writeln!(output).unwrap();
+        }
+
+        for note in &self.notes {
+            writeln!(output, "{gutter} = note: {note}").unwrap();
+        }
+
+        // A trace for an error at the top level would only repeat the
+        // location above.
+        if self.trace.len() > 1 {
+            for frame in &self.trace {
+                writeln!(output, "{frame}").unwrap();
+            }
+        }
+
+        // Leave a blank line between consecutive diagnostics.
+        writeln!(output).unwrap();
+
+        output
+    }
+
+    // Renders the diagnostic exactly as jlox would print it.
+    pub fn render_jlox(&self) -> String {
+        if self.phase == Phase::Runtime {
+            return format!("{}\n[line {}]\n", self.message, self.span.line);
+        }
+
+        let r#where = match &self.label {
+            Some(label) => format!(" {label}"),
+            None => String::new(),
+        };
+
+        format!("[line {}] Error{where}: {}\n", self.span.line, self.message)
+    }
+
+    // Renders the diagnostic as a single line of JSON for tools to consume:
+    //
+    //     {"severity":"error","code":"L0003","message":"Expect expression.",
+    //      "file":"script.lox","span":{"offset":6,"length":1,"line":1,
+    //      "column":7},"lexeme":";","label":"at ';'","notes":[],"stack":[]}
+    pub fn render_json(&self, path: &str, source: &str) -> String {
+        let lexeme = source.get(self.span.offset..self.span.end()).unwrap_or("");
+
+        let label = match &self.label {
+            Some(label) => json_string(label),
+            None => "null".to_string(),
+        };
+
+        let notes: Vec<String> = self.notes.iter().map(|note| json_string(note)).collect();
+
+        let stack: Vec<String> = self
+            .trace
+            .iter()
+            .map(|frame| {
+                let function = match &frame.function {
+                    Some(function) => json_string(function),
+                    None => "null".to_string(),
+                };
+
+                format!("{{\"function\":{function},\"line\":{}}}", frame.line)
+            })
+            .collect();
+
+        format!(
+            "{{\"severity\":{},\"code\":{},\"message\":{},\"file\":{},\"span\":{{\"offset\":{},\"length\":{},\"line\":{},\"column\":{}}},\"lexeme\":{},\"label\":{},\"notes\":[{}],\"stack\":[{}]}}",
+            json_string(&self.severity.to_string()),
+            json_string(self.code.code()),
+            json_string(&self.message),
+            json_string(path),
+            self.span.offset,
+            self.span.length,
+            self.span.line,
+            self.span.column,
+            json_string(lexeme),
+            label,
+            notes.join(","),
+            stack.join(","),
+        )
+    }
+
+    // The number of characters covered by the span, as opposed to bytes.
+    fn underlined_chars(&self, source: &str) -> usize {
+        source
+            .get(self.span.offset..self.span.end())
+            .map_or(self.span.length, |text| text.chars().count())
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        let mut diagnostic =
+            Diagnostic::new(error.code, error.message, error.span).with_notes(error.notes);
+        diagnostic.trace = error.trace;
+        diagnostic
+    }
+}
+
+// Collects the diagnostics reported while scanning, parsing, resolving and
+// running a program, so the host can decide how to show them.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics {
+            diagnostics: vec![],
+        }
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn error(&mut self, code: ErrorCode, span: Span, message: &str) {
+        self.push(Diagnostic::new(code, message, span));
+    }
+
+    pub fn error_at_token(&mut self, code: ErrorCode, token: &Token, message: &str) {
+        self.push(Diagnostic::at_token(code, token, message));
+    }
+
+    // Whether the program failed to compile, which stops it from running.
+    pub fn had_error(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.is_error() && diagnostic.phase != Phase::Runtime)
+    }
+
+    pub fn had_runtime_error(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.is_error() && diagnostic.phase == Phase::Runtime)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.diagnostics.iter()
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+impl<'d> IntoIterator for &'d Diagnostics {
+    type Item = &'d Diagnostic;
+    type IntoIter = std::slice::Iter<'d, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.iter()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
@@ -1,10 +1,15 @@
 use std::{collections::HashMap, cell::RefCell, rc::Rc};
 
-use crate::{interpreter::{Value, RuntimeError}, token::Token};
+use crate::{
+    error_code::ErrorCode,
+    heap::{Trace, Tracer},
+    interpreter::{RuntimeError, Value},
+    token::Token,
+};
 
 pub struct Environment<'a> {
     enclosing: Option<Rc<RefCell<Environment<'a>>>>,
-    values: HashMap<String, &'a Value>
+    values: HashMap<String, Value<'a>>
 }
 
 impl<'a> Environment<'a> {
@@ -21,22 +26,26 @@ impl<'a> Environment<'a> {
         }))
     }
 
-    pub fn get(&self, name: &Token) -> Result<&'a Value, RuntimeError> {
+    pub fn get(&self, name: &Token) -> Result<Value<'a>, RuntimeError> {
         let value = self.values.get(&name.lexeme);
 
         match value {
-            Some(value) => Ok(value),
+            Some(value) => Ok(value.clone()),
             None => {
                 if let Some(enclosing) = &self.enclosing {
                     return enclosing.borrow().get(name);
                 }
 
-                Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme)))
+                Err(RuntimeError::new(
+                    ErrorCode::UndefinedVariable,
+                    name,
+                    format!("Undefined variable '{}'.", name.lexeme),
+                ))
             }
         }
     }
 
-    pub fn assign(&mut self, name: &Token, value: &'a Value) -> Result<(), RuntimeError> {
+    pub fn assign(&mut self, name: &Token, value: Value<'a>) -> Result<(), RuntimeError> {
         if self.values.contains_key(&name.lexeme) {
             self.values.insert(name.lexeme.clone(), value);
             return Ok(());
@@ -47,10 +56,73 @@ impl<'a> Environment<'a> {
             return Ok(());
         }
 
-        Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme)))
+        Err(RuntimeError::new(
+            ErrorCode::UndefinedVariable,
+            name,
+            format!("Undefined variable '{}'.", name.lexeme),
+        ))
     }
 
-    pub fn define(&mut self, name: String, value: &'a Value) {
+    // Looks up a variable the resolver found `distance` scopes away, without
+    // searching every enclosing environment by name on the way there.
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Value<'a>> {
+        if distance == 0 {
+            return self.values.get(name).cloned();
+        }
+
+        self.enclosing
+            .as_ref()
+            .and_then(|enclosing| enclosing.borrow().get_at(distance - 1, name))
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &str, value: Value<'a>) {
+        if distance == 0 {
+            self.values.insert(name.to_string(), value);
+            return;
+        }
+
+        if let Some(enclosing) = &self.enclosing {
+            enclosing.borrow_mut().assign_at(distance - 1, name, value);
+        }
+    }
+
+    pub fn define(&mut self, name: String, value: Value<'a>) {
         self.values.insert(name, value);
     }
-}
\ No newline at end of file
+
+    // The variables defined directly in this environment, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Value<'a>)> {
+        let mut bindings: Vec<_> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        bindings.sort_by(|(left, _), (right, _)| left.cmp(right));
+        bindings
+    }
+}
+
+impl<'a> Trace<'a> for RefCell<Environment<'a>> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(environment) = self.try_borrow() else {
+            tracer.opaque();
+            return;
+        };
+
+        if let Some(enclosing) = &environment.enclosing {
+            tracer.object(enclosing);
+        }
+
+        for value in environment.values.values() {
+            tracer.value(value);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut environment) = self.try_borrow_mut() {
+            environment.enclosing = None;
+            environment.values.clear();
+        }
+    }
+}
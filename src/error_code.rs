@@ -0,0 +1,624 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::diagnostic::Phase;
+
+// A stable identifier for every kind of error Lox can report, so logs and
+// documentation can refer to `L0003` instead of the exact wording of the
+// message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    UnexpectedCharacter,
+    UnterminatedString,
+    ExpectExpression,
+    ExpectSemicolon,
+    ExpectParen,
+    ExpectBrace,
+    ExpectName,
+    ExpectDot,
+    InvalidAssignmentTarget,
+    TooManyParameters,
+    TooManyArguments,
+    AlreadyDeclared,
+    ReadInOwnInitializer,
+    TopLevelReturn,
+    ReturnValueFromInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    InheritFromSelf,
+    OperandNotNumber,
+    OperandsNotNumbers,
+    OperandsNotAddable,
+    UndefinedVariable,
+    UndefinedProperty,
+    NotCallable,
+    WrongArity,
+    PropertyOnNonInstance,
+    FieldOnNonInstance,
+    SuperclassNotClass,
+    NativeFunctionFailed,
+    UnknownOperator,
+    TooManyConstants,
+    TooManyLocals,
+    TooManyUpvalues,
+    JumpTooLarge,
+    StackOverflow,
+    InvalidBytecode,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 37] = [
+        ErrorCode::UnexpectedCharacter,
+        ErrorCode::UnterminatedString,
+        ErrorCode::ExpectExpression,
+        ErrorCode::ExpectSemicolon,
+        ErrorCode::ExpectParen,
+        ErrorCode::ExpectBrace,
+        ErrorCode::ExpectName,
+        ErrorCode::ExpectDot,
+        ErrorCode::InvalidAssignmentTarget,
+        ErrorCode::TooManyParameters,
+        ErrorCode::TooManyArguments,
+        ErrorCode::AlreadyDeclared,
+        ErrorCode::ReadInOwnInitializer,
+        ErrorCode::TopLevelReturn,
+        ErrorCode::ReturnValueFromInitializer,
+        ErrorCode::ThisOutsideClass,
+        ErrorCode::SuperOutsideClass,
+        ErrorCode::SuperWithoutSuperclass,
+        ErrorCode::InheritFromSelf,
+        ErrorCode::OperandNotNumber,
+        ErrorCode::OperandsNotNumbers,
+        ErrorCode::OperandsNotAddable,
+        ErrorCode::UndefinedVariable,
+        ErrorCode::UndefinedProperty,
+        ErrorCode::NotCallable,
+        ErrorCode::WrongArity,
+        ErrorCode::PropertyOnNonInstance,
+        ErrorCode::FieldOnNonInstance,
+        ErrorCode::SuperclassNotClass,
+        ErrorCode::NativeFunctionFailed,
+        ErrorCode::UnknownOperator,
+        ErrorCode::TooManyConstants,
+        ErrorCode::TooManyLocals,
+        ErrorCode::TooManyUpvalues,
+        ErrorCode::JumpTooLarge,
+        ErrorCode::StackOverflow,
+        ErrorCode::InvalidBytecode,
+    ];
+
+    // Codes are numbered in the order the variants are declared. New codes
+    // must only ever be added at the end so existing ones never change.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "L0001",
+            ErrorCode::UnterminatedString => "L0002",
+            ErrorCode::ExpectExpression => "L0003",
+            ErrorCode::ExpectSemicolon => "L0004",
+            ErrorCode::ExpectParen => "L0005",
+            ErrorCode::ExpectBrace => "L0006",
+            ErrorCode::ExpectName => "L0007",
+            ErrorCode::ExpectDot => "L0008",
+            ErrorCode::InvalidAssignmentTarget => "L0009",
+            ErrorCode::TooManyParameters => "L0010",
+            ErrorCode::TooManyArguments => "L0011",
+            ErrorCode::AlreadyDeclared => "L0012",
+            ErrorCode::ReadInOwnInitializer => "L0013",
+            ErrorCode::TopLevelReturn => "L0014",
+            ErrorCode::ReturnValueFromInitializer => "L0015",
+            ErrorCode::ThisOutsideClass => "L0016",
+            ErrorCode::SuperOutsideClass => "L0017",
+            ErrorCode::SuperWithoutSuperclass => "L0018",
+            ErrorCode::InheritFromSelf => "L0019",
+            ErrorCode::OperandNotNumber => "L0020",
+            ErrorCode::OperandsNotNumbers => "L0021",
+            ErrorCode::OperandsNotAddable => "L0022",
+            ErrorCode::UndefinedVariable => "L0023",
+            ErrorCode::UndefinedProperty => "L0024",
+            ErrorCode::NotCallable => "L0025",
+            ErrorCode::WrongArity => "L0026",
+            ErrorCode::PropertyOnNonInstance => "L0027",
+            ErrorCode::FieldOnNonInstance => "L0028",
+            ErrorCode::SuperclassNotClass => "L0029",
+            ErrorCode::NativeFunctionFailed => "L0030",
+            ErrorCode::UnknownOperator => "L0031",
+            ErrorCode::TooManyConstants => "L0032",
+            ErrorCode::TooManyLocals => "L0033",
+            ErrorCode::TooManyUpvalues => "L0034",
+            ErrorCode::JumpTooLarge => "L0035",
+            ErrorCode::StackOverflow => "L0036",
+            ErrorCode::InvalidBytecode => "L0037",
+        }
+    }
+
+    pub fn phase(self) -> Phase {
+        match self {
+            ErrorCode::UnexpectedCharacter | ErrorCode::UnterminatedString => Phase::Scan,
+            ErrorCode::ExpectExpression
+            | ErrorCode::ExpectSemicolon
+            | ErrorCode::ExpectParen
+            | ErrorCode::ExpectBrace
+            | ErrorCode::ExpectName
+            | ErrorCode::ExpectDot
+            | ErrorCode::InvalidAssignmentTarget
+            | ErrorCode::TooManyParameters
+            | ErrorCode::TooManyArguments => Phase::Parse,
+            ErrorCode::AlreadyDeclared
+            | ErrorCode::ReadInOwnInitializer
+            | ErrorCode::TopLevelReturn
+            | ErrorCode::ReturnValueFromInitializer
+            | ErrorCode::ThisOutsideClass
+            | ErrorCode::SuperOutsideClass
+            | ErrorCode::SuperWithoutSuperclass
+            | ErrorCode::InheritFromSelf => Phase::Resolve,
+            ErrorCode::TooManyConstants
+            | ErrorCode::TooManyLocals
+            | ErrorCode::TooManyUpvalues
+            | ErrorCode::JumpTooLarge => Phase::Compile,
+            _ => Phase::Runtime,
+        }
+    }
+
+    // The longer description printed by `rusty-lox --explain`, with an
+    // example that triggers the error.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => {
+                "The source contains a character that is not part of Lox's syntax.
+
+Lox only understands ASCII punctuation such as `+`, `-`, `{` and `;`,
+letters, digits and whitespace. Anything else, including characters like
+`@`, `#` or `|`, is reported and skipped.
+
+Example:
+
+    print 1 | 2; // `|` is not an operator in Lox
+
+Lox has no bitwise operators. Use `or` for a logical or."
+            }
+            ErrorCode::UnterminatedString => {
+                "A string literal was opened with `\"` but never closed.
+
+Strings may span several lines, so the scanner keeps reading until the end
+of the file looking for the closing quote.
+
+Example:
+
+    print \"hello;
+
+Add the missing `\"` at the end of the string."
+            }
+            ErrorCode::ExpectExpression => {
+                "The parser expected an expression but found something else.
+
+This usually means an operand is missing, or a statement keyword was used
+where a value belongs.
+
+Example:
+
+    var total = 1 + ;
+
+Give the operator a right-hand operand, such as `1 + 2`."
+            }
+            ErrorCode::ExpectSemicolon => {
+                "A statement is missing its terminating `;`.
+
+Expression statements, `print`, `var` and `return` statements, as well as
+the clauses of a `for` loop, all end with a semicolon.
+
+Example:
+
+    print \"hello\"
+    print \"world\";
+
+Add `;` after `print \"hello\"`."
+            }
+            ErrorCode::ExpectParen => {
+                "A `(` or `)` is missing.
+
+Conditions of `if` and `while`, the clauses of `for`, parameter lists,
+argument lists and grouped expressions all have to be wrapped in
+parentheses.
+
+Example:
+
+    if x > 1 print x;
+
+Write the condition as `if (x > 1)`."
+            }
+            ErrorCode::ExpectBrace => {
+                "A `{` or `}` is missing.
+
+Function, method and class bodies must be wrapped in braces, and every
+block has to be closed.
+
+Example:
+
+    fun greet() print \"hi\";
+
+Function bodies are blocks: `fun greet() { print \"hi\"; }`."
+            }
+            ErrorCode::ExpectName => {
+                "An identifier was expected.
+
+Variable, function, class, parameter and property names must be
+identifiers: a letter or `_` followed by letters, digits or `_`. Keywords
+cannot be used as names.
+
+Example:
+
+    var class = \"reserved\";
+
+Pick a name that is not a keyword, such as `var kind = \"reserved\";`."
+            }
+            ErrorCode::ExpectDot => {
+                "`super` was used without accessing a method on it.
+
+`super` is not a value by itself. It can only be used to look up a method
+of the superclass.
+
+Example:
+
+    class B < A {
+      method() {
+        super();
+      }
+    }
+
+Call a specific method instead: `super.method();`."
+            }
+            ErrorCode::InvalidAssignmentTarget => {
+                "The left-hand side of `=` is not something that can be assigned to.
+
+Only variables and object fields can be assigned.
+
+Example:
+
+    var a = 1;
+    a + 1 = 3;
+
+Assign to a variable or a field, such as `a = 3;` or `object.field = 3;`."
+            }
+            ErrorCode::TooManyParameters => {
+                "A function declares more than 255 parameters.
+
+Lox limits functions to 255 parameters so calls stay compatible with the
+bytecode interpreter from the book.
+
+Example:
+
+    fun f(a1, a2, a3, /* ... */ a256) {}
+
+Group related parameters into an instance of a class instead."
+            }
+            ErrorCode::TooManyArguments => {
+                "A call passes more than 255 arguments.
+
+Lox limits calls to 255 arguments, the same as the limit on parameters.
+
+Example:
+
+    f(1, 2, 3, /* ... */ 256);
+
+Pass the values in an instance of a class instead."
+            }
+            ErrorCode::AlreadyDeclared => {
+                "A local variable is declared twice in the same scope.
+
+Global variables may be redeclared, but inside a block or function each
+name can only be declared once per scope.
+
+Example:
+
+    {
+      var a = 1;
+      var a = 2;
+    }
+
+Assign to the existing variable with `a = 2;` instead."
+            }
+            ErrorCode::ReadInOwnInitializer => {
+                "A local variable is used in its own initializer.
+
+The variable being declared does not exist until its initializer has been
+evaluated, so it cannot refer to itself.
+
+Example:
+
+    var a = \"outer\";
+    {
+      var a = a;
+    }
+
+Give the inner variable a different name."
+            }
+            ErrorCode::TopLevelReturn => {
+                "A `return` statement appears outside of any function.
+
+Only functions and methods can return.
+
+Example:
+
+    return 1;
+
+Move the `return` into a function body."
+            }
+            ErrorCode::ReturnValueFromInitializer => {
+                "An `init` method returns a value.
+
+Initializers always return the instance they initialized. An empty
+`return;` is allowed to exit early, but returning a value is not.
+
+Example:
+
+    class Point {
+      init() {
+        return 1;
+      }
+    }
+
+Use `return;` or store the value in a field."
+            }
+            ErrorCode::ThisOutsideClass => {
+                "`this` is used outside of a method.
+
+`this` refers to the instance a method was called on, so it only exists
+inside class bodies.
+
+Example:
+
+    fun notAMethod() {
+      print this;
+    }
+
+Move the function into a class, or pass the instance as a parameter."
+            }
+            ErrorCode::SuperOutsideClass => {
+                "`super` is used outside of a method.
+
+`super` looks up methods on the superclass of the enclosing class, so it
+only exists inside class bodies.
+
+Example:
+
+    super.method();
+
+Use `super` inside a method of a class that has a superclass."
+            }
+            ErrorCode::SuperWithoutSuperclass => {
+                "`super` is used in a class that does not inherit from anything.
+
+Example:
+
+    class Base {
+      method() {
+        super.method();
+      }
+    }
+
+Declare a superclass with `class Base < Other`, or call the method on
+`this` instead."
+            }
+            ErrorCode::InheritFromSelf => {
+                "A class names itself as its superclass.
+
+Example:
+
+    class Oops < Oops {}
+
+Inherit from a different class."
+            }
+            ErrorCode::OperandNotNumber => {
+                "Unary `-` was applied to a value that is not a number.
+
+Example:
+
+    print -\"text\";
+
+Only negate numbers."
+            }
+            ErrorCode::OperandsNotNumbers => {
+                "An arithmetic or comparison operator was applied to values that are
+not both numbers.
+
+`-`, `*`, `/`, `<`, `<=`, `>` and `>=` only work on numbers.
+
+Example:
+
+    print \"a\" - 1;
+
+Convert the values to numbers first, or use `+` to join strings."
+            }
+            ErrorCode::OperandsNotAddable => {
+                "`+` was applied to values that are not two numbers or two strings.
+
+`+` adds numbers and concatenates strings, but does not mix the two.
+
+Example:
+
+    print \"count: \" + 3;
+
+Lox has no implicit conversion to strings, so print the parts separately."
+            }
+            ErrorCode::UndefinedVariable => {
+                "A variable was used before being declared.
+
+Globals are looked up when the code runs, so the declaration has to have
+executed before the variable is read or assigned.
+
+Example:
+
+    print total;
+    var total = 1;
+
+Declare the variable with `var` before using it."
+            }
+            ErrorCode::UndefinedProperty => {
+                "An instance has no field or method with the given name.
+
+Example:
+
+    class Point {}
+    print Point().x;
+
+Set the field first, for example in `init`, or define the method on the
+class."
+            }
+            ErrorCode::NotCallable => {
+                "A value that is not a function or a class was called.
+
+Example:
+
+    var name = \"lox\";
+    name();
+
+Only functions, methods and classes can be called."
+            }
+            ErrorCode::WrongArity => {
+                "A function was called with the wrong number of arguments.
+
+Lox has no default or variadic parameters, so every call must pass exactly
+as many arguments as the function declares.
+
+Example:
+
+    fun add(a, b) { return a + b; }
+    add(1);
+
+Pass one argument for every parameter."
+            }
+            ErrorCode::PropertyOnNonInstance => {
+                "A property was read from a value that is not an instance.
+
+Only instances of classes have fields and methods.
+
+Example:
+
+    var number = 1;
+    print number.value;
+
+Read properties from instances only."
+            }
+            ErrorCode::FieldOnNonInstance => {
+                "A field was set on a value that is not an instance.
+
+Only instances of classes can hold fields.
+
+Example:
+
+    var number = 1;
+    number.value = 2;
+
+Set fields on instances only."
+            }
+            ErrorCode::SuperclassNotClass => {
+                "A class inherits from a value that is not a class.
+
+Example:
+
+    var NotAClass = \"string\";
+    class Subclass < NotAClass {}
+
+Inherit from a class."
+            }
+            ErrorCode::NativeFunctionFailed => {
+                "A built-in function such as `clock()` failed.
+
+The message describes what went wrong inside the native function.
+
+Example:
+
+    print clock(); // fails if the system clock is before 1970"
+            }
+            ErrorCode::UnknownOperator => {
+                "The interpreter met an operator it does not know how to evaluate.
+
+The parser never produces such an expression, so this indicates a bug in
+the interpreter rather than in the Lox program. Please report it along
+with the script that caused it."
+            }
+            ErrorCode::TooManyConstants => {
+                "A function uses more constants than fit in one bytecode chunk.
+
+Only reported by the bytecode VM (`--backend=vm`). Every number, string,
+function and global variable name a function mentions takes up a slot in
+its constant pool, which holds at most 65536 entries.
+
+Split the code into several functions, each with its own constant pool."
+            }
+            ErrorCode::TooManyLocals => {
+                "A function declares more local variables than the VM can address.
+
+Only reported by the bytecode VM (`--backend=vm`), which allows at most 256
+local variables, including parameters, to be in scope at once in a single
+function.
+
+Example:
+
+    fun f() {
+        var a0; var a1; // ... and so on up to a256
+
+Move some of the variables into a separate function or an instance."
+            }
+            ErrorCode::TooManyUpvalues => {
+                "A function captures more variables from enclosing functions than
+the VM can address.
+
+Only reported by the bytecode VM (`--backend=vm`), which allows a closure to
+capture at most 256 variables.
+
+Group the captured variables into an instance and capture that instead."
+            }
+            ErrorCode::JumpTooLarge => {
+                "The body of an `if`, loop or `and`/`or` expression compiles to more
+bytecode than a jump can skip over.
+
+Only reported by the bytecode VM (`--backend=vm`), whose jumps cover at most
+65535 bytes of code.
+
+Move part of the body into a function."
+            }
+            ErrorCode::StackOverflow => {
+                "Functions called each other too deeply.
+
+At most 1024 calls can be in progress at once, including the script
+itself. This is almost always caused by recursion that never stops.
+
+Example:
+
+    fun forever() { forever(); }
+    forever();
+
+Make sure every recursive function has a case that returns without calling
+itself again."
+            }
+            ErrorCode::InvalidBytecode => {
+                "The VM ran an instruction on a value it can't work with, such as
+looking up a `super` method on something that isn't a class.
+
+Only reported by the bytecode VM (`--backend=vm`), and only for `.loxc` files
+that weren't written by `rusty-lox compile`: the compiler never produces
+such code, and the loader rejects most other mistakes before running it.
+
+Compile the script again from its source."
+            }
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|error_code| error_code.code().eq_ignore_ascii_case(code))
+            .ok_or_else(|| format!("Unknown error code '{code}'."))
+    }
+}
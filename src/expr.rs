@@ -1,7 +1,7 @@
-use crate::{literal::Literal, token::Token};
+use crate::{literal::Literal, span::Span, token::Token};
 
-pub trait Visitor<R> {
-    fn visit_expr(&mut self, expr: &Expr) -> R;
+pub trait Visitor<'a, R> {
+    fn visit_expr(&mut self, expr: &Expr<'a>) -> R;
 }
 
 pub enum Expr<'a> {
@@ -14,13 +14,63 @@ pub enum Expr<'a> {
         operator: &'a Token,
         right: &'a Expr<'a>,
     },
+    Call {
+        callee: &'a Expr<'a>,
+        paren: &'a Token,
+        arguments: Vec<&'a Expr<'a>>,
+    },
+    Get {
+        object: &'a Expr<'a>,
+        name: &'a Token,
+    },
     Grouping {
         expression: &'a Expr<'a>,
+        // Covers the parentheses too, which have no node of their own.
+        span: Span,
+    },
+    Literal {
+        value: &'a Literal,
+        span: Span,
     },
-    Literal(&'a Literal),
+    Logical {
+        left: &'a Expr<'a>,
+        operator: &'a Token,
+        right: &'a Expr<'a>,
+    },
+    Set {
+        object: &'a Expr<'a>,
+        name: &'a Token,
+        value: &'a Expr<'a>,
+    },
+    Super {
+        keyword: &'a Token,
+        method: &'a Token,
+    },
+    This(&'a Token),
     Unary {
         operator: &'a Token,
         right: &'a Expr<'a>,
     },
     Variable(&'a Token)
 }
+
+impl Expr<'_> {
+    // The region of source code this expression was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign { name, value } => name.span.to(value.span()),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                left.span().to(right.span())
+            }
+            Expr::Call { callee, paren, .. } => callee.span().to(paren.span),
+            Expr::Get { object, name } => object.span().to(name.span),
+            Expr::Grouping { span, .. } => *span,
+            Expr::Literal { span, .. } => *span,
+            Expr::Set { object, value, .. } => object.span().to(value.span()),
+            Expr::Super { keyword, method } => keyword.span.to(method.span),
+            Expr::This(keyword) => keyword.span,
+            Expr::Unary { operator, right } => operator.span.to(right.span()),
+            Expr::Variable(name) => name.span,
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    mem,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    interpreter::Value,
+    output::{self, Output},
+};
+
+// clox starts collecting once a megabyte has been allocated and then lets the
+// heap double in size between collections.
+const FIRST_GC: usize = 1024 * 1024;
+const HEAP_GROW_FACTOR: usize = 2;
+
+// Something on the heap that can refer to other heap objects: environments,
+// functions, classes and instances.
+pub trait Trace<'a> {
+    // Reports every heap object this one holds a reference to.
+    fn trace(&self, tracer: &mut Tracer);
+
+    // Drops every reference this object holds, so that a cycle it is part of
+    // falls apart and reference counting can free it.
+    fn clear(&self);
+}
+
+// Collects the heap objects reachable from a single object or from the
+// interpreter's roots.
+#[derive(Default)]
+pub struct Tracer {
+    found: Vec<*const ()>,
+    // Set when an object couldn't be looked into because it was borrowed
+    // while the collector ran.
+    opaque: bool,
+}
+
+impl Tracer {
+    pub fn object<T: ?Sized>(&mut self, object: &Rc<T>) {
+        self.found.push(Rc::as_ptr(object) as *const ());
+    }
+
+    pub fn value(&mut self, value: &Value) {
+        match value {
+            Value::Class(class) => self.object(class),
+            Value::Function(function) => self.object(function),
+            Value::Instance(instance) => self.object(instance),
+            _ => {}
+        }
+    }
+
+    pub fn opaque(&mut self) {
+        self.opaque = true;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcConfig {
+    // Collect on every allocation, to shake out objects that aren't rooted.
+    pub stress: bool,
+    // Write a summary of every collection to the heap's log.
+    pub log: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    // An estimate of the memory held by live heap objects.
+    pub bytes_allocated: usize,
+    // The same estimate for every object ever allocated.
+    pub total_bytes_allocated: usize,
+    pub collections: usize,
+    pub objects_collected: usize,
+    pub bytes_collected: usize,
+}
+
+struct Entry<'a> {
+    object: Weak<dyn Trace<'a> + 'a>,
+    size: usize,
+}
+
+// Keeps track of every heap object so that cycles can be found and freed.
+//
+// Values are reference counted, which frees most garbage as soon as it
+// becomes unreachable, but not cycles such as an instance holding a method
+// bound to itself. The heap only holds weak references, and a collection
+// marks everything reachable from the roots and clears whatever is left.
+//
+// The roots are the environments the interpreter hands in, along with every
+// object referenced from outside the heap: one that has more strong
+// references than the heap objects pointing at it accounts for is being held
+// by the interpreter's Rust call stack, such as an argument list being built
+// or the environment of a call in progress.
+pub struct Heap<'a> {
+    objects: Vec<Entry<'a>>,
+    next_gc: usize,
+    config: GcConfig,
+    stats: GcStats,
+    log: Output,
+}
+
+impl<'a> Heap<'a> {
+    pub fn new(config: GcConfig) -> Heap<'a> {
+        Heap {
+            objects: vec![],
+            next_gc: FIRST_GC,
+            config,
+            stats: GcStats::default(),
+            log: output::discard(),
+        }
+    }
+
+    pub fn config(&self) -> GcConfig {
+        self.config
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    // Sets where collections are logged when `GcConfig::log` is on.
+    pub fn set_log(&mut self, log: Output) {
+        self.log = log;
+    }
+
+    pub fn log(&self) -> Output {
+        Rc::clone(&self.log)
+    }
+
+    // Starts tracking a newly allocated object. Returns whether it is time
+    // to collect garbage.
+    pub fn track<T: Trace<'a> + 'a>(&mut self, object: &Rc<T>) -> bool {
+        let size = mem::size_of::<T>();
+        let object: Weak<T> = Rc::downgrade(object);
+        let object: Weak<dyn Trace<'a> + 'a> = object;
+        self.objects.push(Entry { object, size });
+
+        self.stats.bytes_allocated += size;
+        self.stats.total_bytes_allocated += size;
+
+        self.config.stress || self.stats.bytes_allocated > self.next_gc
+    }
+
+    pub fn collect(&mut self, mark_roots: impl FnOnce(&mut Tracer)) {
+        if self.config.log {
+            let _ = writeln!(self.log.borrow_mut(), "-- gc begin");
+        }
+
+        let before = self.stats.bytes_allocated;
+
+        // Objects that reference counting already freed only need to be
+        // forgotten.
+        let mut entries = Vec::with_capacity(self.objects.len());
+        let mut live: Vec<Rc<dyn Trace<'a> + 'a>> = Vec::with_capacity(self.objects.len());
+        for entry in mem::take(&mut self.objects) {
+            if let Some(object) = entry.object.upgrade() {
+                entries.push(entry);
+                live.push(object);
+            }
+        }
+
+        let index: HashMap<*const (), usize> = live
+            .iter()
+            .enumerate()
+            .map(|(index, object)| (Rc::as_ptr(object) as *const (), index))
+            .collect();
+
+        let mut children = vec![vec![]; live.len()];
+        let mut internal = vec![0; live.len()];
+        let mut gray = vec![];
+
+        for (parent, object) in live.iter().enumerate() {
+            let mut tracer = Tracer::default();
+            object.trace(&mut tracer);
+
+            for child in tracer.found {
+                if let Some(&child) = index.get(&child) {
+                    internal[child] += 1;
+                    children[parent].push(child);
+                }
+            }
+
+            // Whatever an object we couldn't look into refers to is still
+            // counted as referenced from outside, so keeping the object
+            // itself is enough.
+            if tracer.opaque {
+                gray.push(parent);
+            }
+        }
+
+        // One strong reference is the one `live` holds for the collection.
+        for (object_index, object) in live.iter().enumerate() {
+            if Rc::strong_count(object) - 1 > internal[object_index] {
+                gray.push(object_index);
+            }
+        }
+
+        let mut tracer = Tracer::default();
+        mark_roots(&mut tracer);
+        gray.extend(tracer.found.iter().filter_map(|root| index.get(root)));
+
+        let mut marked = vec![false; live.len()];
+        while let Some(object) = gray.pop() {
+            if !marked[object] {
+                marked[object] = true;
+                gray.extend(&children[object]);
+            }
+        }
+
+        for (object, _) in live.iter().zip(&marked).filter(|(_, marked)| !**marked) {
+            object.clear();
+        }
+
+        let collected = marked.iter().filter(|marked| !**marked).count();
+        self.objects = entries
+            .into_iter()
+            .zip(marked)
+            .filter_map(|(entry, marked)| marked.then_some(entry))
+            .collect();
+
+        // Dropping the last strong references to the cleared objects frees
+        // them.
+        drop(live);
+
+        let after = self.objects.iter().map(|entry| entry.size).sum();
+        self.stats.bytes_allocated = after;
+        self.stats.collections += 1;
+        self.stats.objects_collected += collected;
+        self.stats.bytes_collected += before.saturating_sub(after);
+        self.next_gc = (after * HEAP_GROW_FACTOR).max(FIRST_GC);
+
+        if self.config.log {
+            let mut log = self.log.borrow_mut();
+            let _ = writeln!(log, "-- gc end");
+            let _ = writeln!(
+                log,
+                "   collected {} bytes (from {before} to {after}) next at {}",
+                before.saturating_sub(after),
+                self.next_gc
+            );
+            let _ = writeln!(log, "   freed {collected} unreachable objects");
+        }
+    }
+}
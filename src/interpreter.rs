@@ -1,79 +1,387 @@
-use std::{cell::RefCell, rc::Rc};
-
-use typed_arena::Arena;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Display,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
     environment::Environment,
-    expr::Expr,
+    error_code::ErrorCode,
+    expr::{Expr, Visitor as ExprVisitor},
+    heap::{GcConfig, GcStats, Heap, Trace},
     literal::Literal,
-    stmt::Stmt,
+    lox_callable::LoxCallable,
+    lox_class::LoxClass,
+    lox_function::LoxFunction,
+    lox_instance::LoxInstance,
+    native_function::NativeFunction,
+    output::{self, Output},
+    span::Span,
+    stmt::{Stmt, Visitor as StmtVisitor},
     token::Token,
     token_type::TokenType,
-    Context,
 };
 
-#[derive(PartialEq)]
-pub enum Value {
+// How many calls can be in progress at once before the interpreter reports a
+// stack overflow, counting the script itself the way the VM does, so that
+// both backends stop at the same call.
+const MAX_FRAMES: usize = 1024;
+
+// Every Lox call recurses on the native stack, through `evaluate`. When less
+// than the red zone is left, `evaluate` carries on in a newly allocated
+// segment, so that recursion reaches MAX_FRAMES instead of overflowing the
+// host's stack, however small it is.
+const STACK_RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+#[derive(Clone)]
+pub enum Value<'a> {
     Bool(bool),
+    Class(Rc<LoxClass<'a>>),
+    Function(Rc<LoxFunction<'a>>),
+    Instance(Rc<RefCell<LoxInstance<'a>>>),
+    NativeFunction(Rc<NativeFunction>),
     Number(f64),
-    String(String),
+    String(Rc<str>),
     Nil,
 }
 
+impl Value<'_> {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "a boolean",
+            Value::Class(_) => "a class",
+            Value::Function(_) => "a function",
+            Value::Instance(_) => "an instance",
+            Value::NativeFunction(_) => "a native function",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Nil => "nil",
+        }
+    }
+}
+
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            (Value::NativeFunction(left), Value::NativeFunction(right)) => Rc::ptr_eq(left, right),
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<Literal> for Value<'_> {
+    fn from(literal: Literal) -> Self {
+        match literal {
+            Literal::Bool(bool) => Value::Bool(bool),
+            Literal::Number(number) => Value::Number(number),
+            Literal::String(string) => Value::String(string.into()),
+            Literal::Nil => Value::Nil,
+        }
+    }
+}
+
+impl TryFrom<&Value<'_>> for Literal {
+    type Error = RuntimeError;
+
+    fn try_from(value: &Value<'_>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(bool) => Ok(Literal::Bool(*bool)),
+            Value::Number(number) => Ok(Literal::Number(*number)),
+            Value::String(string) => Ok(Literal::String(string.to_string())),
+            Value::Nil => Ok(Literal::Nil),
+            _ => Err(RuntimeError::not_a_literal(value.type_name())),
+        }
+    }
+}
+
 pub struct RuntimeError {
-    pub token_line: usize,
+    pub code: ErrorCode,
+    pub span: Span,
     pub message: String,
+    pub notes: Vec<String>,
+    // The calls that were active when the error happened, innermost first
+    // and ending with the top-level script.
+    pub trace: Vec<StackFrame>,
 }
 
 impl RuntimeError {
-    pub fn new<T: Into<String>>(token: &Token, message: T) -> RuntimeError {
+    pub(crate) fn new<T: Into<String>>(code: ErrorCode, token: &Token, message: T) -> RuntimeError {
         RuntimeError {
-            token_line: token.line,
+            code,
+            span: token.span,
             message: message.into(),
+            notes: vec![],
+            trace: vec![],
         }
     }
+
+    // For errors found by the VM, which only knows the span each
+    // instruction was compiled from.
+    pub(crate) fn at<T: Into<String>>(code: ErrorCode, span: Span, message: T) -> RuntimeError {
+        RuntimeError {
+            code,
+            span,
+            message: message.into(),
+            notes: vec![],
+            trace: vec![],
+        }
+    }
+
+    // Native functions have no token to point at, so the interpreter
+    // attributes their errors to the line of the call instead.
+    pub fn from_native<T: Into<String>>(message: T) -> RuntimeError {
+        RuntimeError {
+            code: ErrorCode::NativeFunctionFailed,
+            span: Span::default(),
+            message: message.into(),
+            notes: vec![],
+            trace: vec![],
+        }
+    }
+
+    // For natives registered with `Lox::define_native`, which can only be
+    // passed values that both backends share.
+    pub(crate) fn not_a_literal(type_name: &str) -> RuntimeError {
+        RuntimeError::from_native(
+            "Native functions only take numbers, strings, booleans and nil.",
+        )
+        .with_note(format!("the argument is {type_name}"))
+    }
+
+    pub fn with_note<T: Into<String>>(mut self, note: T) -> RuntimeError {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+// One line of a stack trace: the function that was running, or the
+// top-level script when `function` is `None`, and the line it was executing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub function: Option<String>,
+    pub line: usize,
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "[line {}] in {function}()", self.line),
+            None => write!(f, "[line {}] in script", self.line),
+        }
+    }
+}
+
+// Executing a statement can be cut short either by a runtime error or by a
+// return statement unwinding to the enclosing function call.
+pub enum Unwind<'a> {
+    Error(RuntimeError),
+    Return(Value<'a>),
+}
+
+impl From<RuntimeError> for Unwind<'_> {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
 }
 
 pub struct Interpreter<'a> {
+    globals: Rc<RefCell<Environment<'a>>>,
     environment: Rc<RefCell<Environment<'a>>>,
-    arena: &'a Arena<Value>
+    // How many scopes away each resolved local variable lives, keyed by the
+    // address of the variable or assignment expression in the syntax tree.
+    locals: HashMap<*const Expr<'a>, usize>,
+    // The Lox functions currently being called, outermost first, each with
+    // the line it was called from.
+    call_stack: Vec<StackFrame>,
+    heap: Heap<'a>,
+    // Where `print` statements write.
+    output: Output,
 }
 
 impl<'a> Interpreter<'a> {
-    pub fn new(arena: &'a Arena<Value>) -> Interpreter<'a> {
-        Interpreter {
-            environment: Environment::new(),
-            arena
-        }
+    pub fn new() -> Interpreter<'a> {
+        Interpreter::with_gc(GcConfig::default())
     }
 
-    pub fn interpret(&mut self, context: &RefCell<Context>, statements: Vec<Stmt>) {
+    pub fn with_gc(config: GcConfig) -> Interpreter<'a> {
+        let globals = Environment::new();
+
+        let mut interpreter = Interpreter {
+            environment: globals.clone(),
+            globals,
+            locals: HashMap::new(),
+            call_stack: vec![],
+            heap: Heap::new(config),
+            output: output::stdout(),
+        };
+        interpreter.track(&interpreter.globals.clone());
+
+        interpreter.define_native("clock", 0, |_| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| RuntimeError::from_native("System clock is before the Unix epoch."))?;
+
+            Ok(Value::Number(now.as_secs_f64()))
+        });
+
+        interpreter
+    }
+
+    // Exposes a function implemented in Rust to Lox code as a global.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: for<'v> Fn(&[Value<'v>]) -> Result<Value<'v>, RuntimeError> + 'static,
+    {
+        let native = NativeFunction::new(arity, Box::new(function));
+        let native = Value::NativeFunction(Rc::new(native));
+
+        self.globals.borrow_mut().define(name.to_string(), native);
+    }
+
+    // Runs the statements in order. Like jlox, a runtime error aborts the
+    // rest of the script and is handed back to the caller to report.
+    pub fn interpret(&mut self, statements: Vec<Stmt<'a>>) -> Result<(), RuntimeError> {
         for statement in statements {
-            let result = self.execute(&statement);
-            if let Err(error) = result {
-                context.borrow_mut().runtime_error(error);
+            if let Err(Unwind::Error(error)) = self.execute(&statement) {
+                return Err(self.capture_trace(error));
             }
         }
+
+        Ok(())
+    }
+
+    // Records the call stack on an error the first time it unwinds out of a
+    // call, while the frames it happened in are still known.
+    fn capture_trace(&self, mut error: RuntimeError) -> RuntimeError {
+        if !error.trace.is_empty() {
+            return error;
+        }
+
+        // Each frame is executing the line its callee was called from, apart
+        // from the innermost one, which is where the error happened.
+        let mut line = error.span.line;
+        for frame in self.call_stack.iter().rev() {
+            error.trace.push(StackFrame {
+                function: frame.function.clone(),
+                line,
+            });
+            line = frame.line;
+        }
+
+        error.trace.push(StackFrame {
+            function: None,
+            line,
+        });
+
+        error
+    }
+
+    // Evaluates a lone expression, which is how the REPL shows the value of
+    // what was typed at the prompt.
+    pub fn interpret_expression(&mut self, expr: &Expr<'a>) -> Result<Value<'a>, RuntimeError> {
+        self.evaluate(expr).map_err(|error| self.capture_trace(error))
+    }
+
+    // The variables defined in the innermost scope being executed, which is
+    // the global scope between statements.
+    pub fn bindings(&self) -> Vec<(String, Value<'a>)> {
+        self.environment.borrow().bindings()
+    }
+
+    pub(crate) fn resolve(&mut self, expr: &Expr<'a>, depth: usize) {
+        self.locals.insert(expr, depth);
+    }
+
+    // Registers a newly allocated environment, function, class or instance
+    // with the garbage collector, which may run a collection right away.
+    pub(crate) fn track<T: Trace<'a> + 'a>(&mut self, object: &Rc<T>) {
+        if self.heap.track(object) {
+            self.collect_garbage();
+        }
+    }
+
+    // Frees every heap object that can no longer be reached, including
+    // cycles that reference counting can't free on its own.
+    pub fn collect_garbage(&mut self) {
+        let globals = &self.globals;
+        let environment = &self.environment;
+
+        self.heap.collect(|tracer| {
+            tracer.object(globals);
+            tracer.object(environment);
+        });
+    }
+
+    pub fn gc_config(&self) -> GcConfig {
+        self.heap.config()
+    }
+
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    pub fn output(&self) -> Output {
+        Rc::clone(&self.output)
+    }
+
+    // Sets where the collector writes its log when `GcConfig::log` is on.
+    pub fn set_log(&mut self, log: Output) {
+        self.heap.set_log(log);
+    }
+
+    pub fn log(&self) -> Output {
+        self.heap.log()
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> Result<&'a Value, RuntimeError> {
-        self.visit_expr(expr)
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+    fn evaluate(&mut self, expr: &Expr<'a>) -> Result<Value<'a>, RuntimeError> {
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || self.visit_expr(expr))
+    }
+
+    fn execute(&mut self, stmt: &Stmt<'a>) -> Result<(), Unwind<'a>> {
         self.visit_stmt(stmt)
     }
 
-    fn execute_block(&mut self, statements: &Vec<Stmt>, environment: Rc<RefCell<Environment<'a>>>) -> Result<(), RuntimeError> {
+    pub(crate) fn execute_block(&mut self, statements: &[Stmt<'a>], environment: Rc<RefCell<Environment<'a>>>) -> Result<(), Unwind<'a>> {
         let previous = std::mem::replace(&mut self.environment, environment);
 
-        for statement in statements {
-            self.execute(statement)?;
-        }
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
 
         self.environment = previous;
 
-        Ok(())
+        result
+    }
+
+    fn look_up_variable(&self, name: &Token, expr: &Expr<'a>) -> Result<Value<'a>, RuntimeError> {
+        match self.locals.get(&(expr as *const Expr<'a>)) {
+            Some(distance) => self
+                .environment
+                .borrow()
+                .get_at(*distance, &name.lexeme)
+                .ok_or_else(|| {
+                    RuntimeError::new(
+                        ErrorCode::UndefinedVariable,
+                        name,
+                        format!("Undefined variable '{}'.", name.lexeme),
+                    )
+                }),
+            None => self.globals.borrow().get(name),
+        }
     }
 
     fn is_truthy(value: &Value) -> bool {
@@ -84,23 +392,68 @@ impl<'a> Interpreter<'a> {
         }
     }
 
-    fn is_equal(a: &Value, b: &Value) -> bool {
+    fn is_equal(a: &Value<'a>, b: &Value<'a>) -> bool {
         a == b
     }
 
-    fn stringify(value: &Value) -> String {
+    pub fn stringify(value: &Value) -> String {
         match value {
             Value::Nil => "nil".to_string(),
-            Value::Number(number) => format!("{number}"),
+            Value::Number(number) => Interpreter::stringify_number(*number),
             Value::Bool(bool) => format!("{bool}"),
-            Value::String(str) => str.clone(),
+            Value::Class(class) => class.to_string(),
+            Value::Function(function) => function.to_string(),
+            Value::Instance(instance) => instance.borrow().to_string(),
+            Value::NativeFunction(native) => native.to_string(),
+            Value::String(str) => str.to_string(),
+        }
+    }
+
+    // Formats numbers the way Java's Double.toString does, since that is what
+    // jlox prints, except that integers don't get a trailing ".0".
+    pub(crate) fn stringify_number(number: f64) -> String {
+        if number.is_nan() {
+            return "NaN".to_string();
+        }
+
+        if number.is_infinite() {
+            return if number > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+        }
+
+        let magnitude = number.abs();
+        if magnitude != 0.0 && !(1e-3..1e7).contains(&magnitude) {
+            // Java switches to scientific notation outside of this range and
+            // always keeps at least one digit after the decimal point.
+            let formatted = format!("{number:e}");
+            let (mantissa, exponent) = formatted.split_once('e').unwrap();
+
+            return if mantissa.contains('.') {
+                format!("{mantissa}E{exponent}")
+            } else {
+                format!("{mantissa}.0E{exponent}")
+            };
         }
+
+        format!("{number}")
+    }
+
+    fn describe_operands(left: &Value, right: &Value) -> String {
+        format!(
+            "the left operand is {} and the right operand is {}",
+            left.type_name(),
+            right.type_name()
+        )
     }
 
     fn check_number_operand(operator: &Token, operand: &Value) -> Result<f64, RuntimeError> {
         match operand {
             Value::Number(num) => Ok(*num),
-            _ => Err(RuntimeError::new(operator, "Operand must be a number.")),
+            _ => Err(RuntimeError::new(
+                ErrorCode::OperandNotNumber,
+                operator,
+                "Operand must be a number.",
+            )
+                .with_note(format!("the operand is {}", operand.type_name()))),
         }
     }
 
@@ -111,40 +464,227 @@ impl<'a> Interpreter<'a> {
     ) -> Result<(f64, f64), RuntimeError> {
         match (left, right) {
             (Value::Number(left), Value::Number(right)) => Ok((*left, *right)),
-            _ => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+            _ => Err(RuntimeError::new(
+                ErrorCode::OperandsNotNumbers,
+                operator,
+                "Operands must be numbers.",
+            )
+                .with_note(Interpreter::describe_operands(left, right))),
         }
     }
 }
 
-impl<'a> Interpreter<'a> {
-    fn visit_expr(&mut self, expr: &Expr) -> Result<&'a Value, RuntimeError> {
+impl Default for Interpreter<'_> {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl<'a> ExprVisitor<'a, Result<Value<'a>, RuntimeError>> for Interpreter<'a> {
+    fn visit_expr(&mut self, expr: &Expr<'a>) -> Result<Value<'a>, RuntimeError> {
         match expr {
-            Expr::Literal(literal) => {
-                match literal {
-                    Literal::Bool(bool) => Ok(self.arena.alloc(Value::Bool(*bool))),
-                    Literal::Number(f64) => Ok(self.arena.alloc(Value::Number(*f64))),
-                    Literal::String(string) => Ok(self.arena.alloc(Value::String(string.clone()))), // Optimize away this clone
-                    Literal::Nil => Ok(self.arena.alloc(Value::Nil)),
+            Expr::Literal { value, .. } => {
+                match value {
+                    Literal::Bool(bool) => Ok(Value::Bool(*bool)),
+                    Literal::Number(f64) => Ok(Value::Number(*f64)),
+                    Literal::String(string) => Ok(Value::String(Rc::from(string.as_str()))),
+                    Literal::Nil => Ok(Value::Nil),
                 }
             }
-            Expr::Grouping { expression } => self.evaluate(expression),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.evaluate(callee)?;
+
+                let mut evaluated_arguments = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    evaluated_arguments.push(self.evaluate(argument)?);
+                }
+
+                let function: &dyn LoxCallable<'a> = match &callee {
+                    Value::Class(class) => class,
+                    Value::Function(function) => function.as_ref(),
+                    Value::NativeFunction(native) => native.as_ref(),
+                    _ => {
+                        return Err(RuntimeError::new(
+                            ErrorCode::NotCallable,
+                            paren,
+                            "Can only call functions and classes.",
+                        )
+                        .with_note(format!("the called value is {}", callee.type_name())))
+                    }
+                };
+
+                if evaluated_arguments.len() != function.arity() {
+                    return Err(RuntimeError::new(
+                        ErrorCode::WrongArity,
+                        paren,
+                        format!(
+                            "Expected {} arguments but got {}.",
+                            function.arity(),
+                            evaluated_arguments.len()
+                        ),
+                    ));
+                }
+
+                // Native functions don't show up in stack traces, and calling a
+                // class runs its initializer.
+                let frame = match &callee {
+                    Value::Function(function) => Some(function.name()),
+                    Value::Class(class) => class.find_method("init").map(|init| init.name()),
+                    _ => None,
+                };
+
+                let pushed = frame.is_some();
+                if let Some(function) = frame {
+                    if self.call_stack.len() + 1 == MAX_FRAMES {
+                        let error =
+                            RuntimeError::new(ErrorCode::StackOverflow, paren, "Stack overflow.");
+                        return Err(self.capture_trace(error));
+                    }
+
+                    self.call_stack.push(StackFrame {
+                        function: Some(function.to_string()),
+                        line: paren.span.line,
+                    });
+                }
+
+                let mut result = function.call(self, evaluated_arguments);
+
+                if let Value::NativeFunction(_) = callee {
+                    result = result.map_err(|error| RuntimeError {
+                        span: paren.span,
+                        ..error
+                    });
+                }
+
+                let result = result.map_err(|error| self.capture_trace(error));
+
+                if pushed {
+                    self.call_stack.pop();
+                }
+
+                result
+            }
+            Expr::Get { object, name } => {
+                let object = self.evaluate(object)?;
+
+                if let Value::Instance(instance) = &object {
+                    return instance.borrow().get(name, &object, self);
+                }
+
+                Err(RuntimeError::new(
+                    ErrorCode::PropertyOnNonInstance,
+                    name,
+                    "Only instances have properties.",
+                )
+                    .with_note(format!("the value is {}", object.type_name())))
+            }
+            Expr::Grouping { expression, .. } => self.evaluate(expression),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+
+                // Short-circuit and hand back the operand that decided the
+                // result instead of coercing it to a bool.
+                if operator.r#type == TokenType::Or {
+                    if Interpreter::is_truthy(&left) {
+                        return Ok(left);
+                    }
+                } else if !Interpreter::is_truthy(&left) {
+                    return Ok(left);
+                }
+
+                self.evaluate(right)
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+
+                let Value::Instance(instance) = &object else {
+                    return Err(RuntimeError::new(
+                        ErrorCode::FieldOnNonInstance,
+                        name,
+                        "Only instances have fields.",
+                    )
+                        .with_note(format!("the value is {}", object.type_name())));
+                };
+
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            Expr::Super { keyword, method } => {
+                let distance = self.locals[&(expr as *const Expr<'a>)];
+                let environment = self.environment.clone();
+                let environment = environment.borrow();
+
+                let superclass = environment.get_at(distance, "super");
+                let Some(Value::Class(superclass)) = superclass else {
+                    return Err(RuntimeError::new(
+                        ErrorCode::UndefinedVariable,
+                        keyword,
+                        "Undefined variable 'super'.",
+                    ));
+                };
+
+                // `this` is always bound in the scope right inside the one
+                // that defines `super`.
+                let object = environment
+                    .get_at(distance - 1, "this")
+                    .ok_or_else(|| {
+                        RuntimeError::new(
+                            ErrorCode::UndefinedVariable,
+                            keyword,
+                            "Undefined variable 'this'.",
+                        )
+                    })?;
+
+                match superclass.find_method(&method.lexeme) {
+                    Some(found) => Ok(Value::Function(found.bind(object, self))),
+                    None => Err(RuntimeError::new(
+                        ErrorCode::UndefinedProperty,
+                        method,
+                        format!("Undefined property '{}'.", method.lexeme),
+                    )),
+                }
+            }
+            Expr::This(keyword) => self.look_up_variable(keyword, expr),
             Expr::Unary { operator, right } => {
                 let right = self.evaluate(right)?;
 
-                if operator.r#type == TokenType::Bang {
-                    return Ok(self.arena.alloc(Value::Bool(!Interpreter::is_truthy(right))));
-                } else if operator.r#type == TokenType::Minus {
-                    let number = Interpreter::check_number_operand(operator, right)?;
-                    return Ok(self.arena.alloc(Value::Number(-number)));
+                match operator.r#type {
+                    TokenType::Bang => {
+                        Ok(Value::Bool(!Interpreter::is_truthy(&right)))
+                    }
+                    TokenType::Minus => {
+                        let number = Interpreter::check_number_operand(operator, &right)?;
+                        Ok(Value::Number(-number))
+                    }
+                    _ => Err(RuntimeError::new(
+                        ErrorCode::UnknownOperator,
+                        operator,
+                        format!("Unknown unary operator '{}'.", operator.lexeme),
+                    )),
                 }
-
-                // Unreachable
-                todo!("Handle this case later!")
             }
-            Expr::Variable(name) => self.environment.borrow().get(name),
+            Expr::Variable(name) => self.look_up_variable(name, expr),
             Expr::Assign { name, value } => {
                 let value = self.evaluate(value)?;
-                self.environment.borrow_mut().assign(name, value)?;
+
+                match self.locals.get(&(expr as *const Expr<'a>)) {
+                    Some(distance) => self.environment.borrow_mut().assign_at(*distance, &name.lexeme, value.clone()),
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+
                 Ok(value)
             },
             Expr::Binary {
@@ -157,100 +697,204 @@ impl<'a> Interpreter<'a> {
 
                 match operator.r#type {
                     TokenType::BangEqual => {
-                        return Ok(self.arena.alloc(Value::Bool(!Interpreter::is_equal(left, right))))
+                        return Ok(Value::Bool(!Interpreter::is_equal(&left, &right)))
                     }
                     TokenType::EqualEqual => {
-                        return Ok(self.arena.alloc(Value::Bool(Interpreter::is_equal(left, right))))
+                        return Ok(Value::Bool(Interpreter::is_equal(&left, &right)))
                     }
                     TokenType::Greater => {
                         let (left, right) =
-                            Interpreter::check_number_operands(operator, left, right)?;
-                        return Ok(self.arena.alloc(Value::Bool(left > right)));
+                            Interpreter::check_number_operands(operator, &left, &right)?;
+                        return Ok(Value::Bool(left > right));
                     }
                     TokenType::GreaterEqual => {
                         let (left, right) =
-                            Interpreter::check_number_operands(operator, left, right)?;
-                        return Ok(self.arena.alloc(Value::Bool(left >= right)));
+                            Interpreter::check_number_operands(operator, &left, &right)?;
+                        return Ok(Value::Bool(left >= right));
                     }
                     TokenType::Less => {
                         let (left, right) =
-                            Interpreter::check_number_operands(operator, left, right)?;
-                        return Ok(self.arena.alloc(Value::Bool(left < right)));
+                            Interpreter::check_number_operands(operator, &left, &right)?;
+                        return Ok(Value::Bool(left < right));
                     }
                     TokenType::LessEqual => {
                         let (left, right) =
-                            Interpreter::check_number_operands(operator, left, right)?;
-                        return Ok(self.arena.alloc(Value::Bool(left <= right)));
+                            Interpreter::check_number_operands(operator, &left, &right)?;
+                        return Ok(Value::Bool(left <= right));
                     }
                     TokenType::Minus => {
                         return {
                             let (left, right) =
-                                Interpreter::check_number_operands(operator, left, right)?;
-                            Ok(self.arena.alloc(Value::Number(left - right)))
+                                Interpreter::check_number_operands(operator, &left, &right)?;
+                            Ok(Value::Number(left - right))
                         }
                     }
                     TokenType::Plus => {
-                        let result = Interpreter::check_number_operands(operator, left, right);
-
-                        if let Ok((left, right)) = result {
-                            return Ok(self.arena.alloc(Value::Number(left + right)));
-                        } else if let (Value::String(left), Value::String(right)) = &(left, right)
-                        {
-                            if operator.r#type == TokenType::Plus {
-                                return Ok(self.arena.alloc(Value::String(format!("{left}{right}"))));
+                        return match (&left, &right) {
+                            (Value::Number(left), Value::Number(right)) => {
+                                Ok(Value::Number(left + right))
+                            }
+                            (Value::String(left), Value::String(right)) => {
+                                Ok(Value::String(format!("{left}{right}").into()))
                             }
-                        } else {
-                            return Err(RuntimeError::new(
+                            _ => Err(RuntimeError::new(
+                                ErrorCode::OperandsNotAddable,
                                 operator,
                                 "Operands must be two numbers or two strings.",
-                            ));
-                        }
+                            )
+                            .with_note(Interpreter::describe_operands(&left, &right))),
+                        };
                     }
                     TokenType::Slash => {
                         let (left, right) =
-                            Interpreter::check_number_operands(operator, left, right)?;
-                        return Ok(self.arena.alloc(Value::Number(left / right)));
+                            Interpreter::check_number_operands(operator, &left, &right)?;
+                        return Ok(Value::Number(left / right));
                     }
                     TokenType::Star => {
                         let (left, right) =
-                            Interpreter::check_number_operands(operator, left, right)?;
-                        return Ok(self.arena.alloc(Value::Number(left * right)));
+                            Interpreter::check_number_operands(operator, &left, &right)?;
+                        return Ok(Value::Number(left * right));
                     }
                     _ => (),
                 }
 
-                // Unreachable
-                todo!("Handle this case later!")
+                Err(RuntimeError::new(
+                    ErrorCode::UnknownOperator,
+                    operator,
+                    format!("Unknown binary operator '{}'.", operator.lexeme),
+                ))
             }
         }
     }
 }
 
-impl<'a> Interpreter<'a> {
-    fn visit_stmt(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+impl<'a> StmtVisitor<'a, Result<(), Unwind<'a>>> for Interpreter<'a> {
+    fn visit_stmt(&mut self, stmt: &Stmt<'a>) -> Result<(), Unwind<'a>> {
         match stmt {
             Stmt::Block { statements } => {
-                self.execute_block(statements, Environment::new_with_enclosing(&self.environment))?;
+                let environment = Environment::new_with_enclosing(&self.environment);
+                self.track(&environment);
+                self.execute_block(statements, environment)?;
+                Ok(())
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(superclass_expr) => match self.evaluate(superclass_expr)? {
+                        Value::Class(class) => Some(class.clone()),
+                        value => {
+                            let Expr::Variable(superclass_name) = superclass_expr else {
+                                unreachable!("superclass is always parsed as a variable");
+                            };
+
+                            return Err(Unwind::Error(
+                                RuntimeError::new(
+                                    ErrorCode::SuperclassNotClass,
+                                    superclass_name,
+                                    "Superclass must be a class.",
+                                )
+                                    .with_note(format!(
+                                        "'{}' is {}",
+                                        superclass_name.lexeme,
+                                        value.type_name()
+                                    )),
+                            ));
+                        }
+                    },
+                    None => None,
+                };
+
+                let enclosing = self.environment.clone();
+                if let Some(superclass) = &superclass {
+                    self.environment = Environment::new_with_enclosing(&enclosing);
+                    self.track(&self.environment.clone());
+                    let superclass = Value::Class(superclass.clone());
+                    self.environment
+                        .borrow_mut()
+                        .define("super".to_string(), superclass);
+                }
+
+                let mut class_methods = HashMap::new();
+                for method in methods {
+                    let function = LoxFunction::new(
+                        method.clone(),
+                        self.environment.clone(),
+                        method.name.lexeme == "init",
+                    );
+                    let function = Rc::new(function);
+                    self.track(&function);
+                    class_methods.insert(method.name.lexeme.clone(), function);
+                }
+
+                self.environment = enclosing;
+
+                let class = LoxClass::new(name.lexeme.clone(), superclass, class_methods);
+                let class = Rc::new(class);
+                self.track(&class);
+                let class = Value::Class(class);
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.clone(), class);
                 Ok(())
             }
             Stmt::Expression(expression) => {
                 self.evaluate(expression)?;
                 Ok(())
             }
+            Stmt::Function(declaration) => {
+                let function = LoxFunction::new(declaration.clone(), self.environment.clone(), false);
+                let function = Rc::new(function);
+                self.track(&function);
+                let function = Value::Function(function);
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.lexeme.clone(), function);
+                Ok(())
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if Interpreter::is_truthy(&self.evaluate(condition)?) {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+                Ok(())
+            }
             Stmt::Print(expression) => {
                 let value = self.evaluate(expression)?;
-                println!("{}", Interpreter::stringify(value));
+                let text = Interpreter::stringify(&value);
+                let _ = writeln!(self.output.borrow_mut(), "{text}");
                 Ok(())
             }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+
+                Err(Unwind::Return(value))
+            }
             Stmt::Var { name, initializer } => {
-                let mut value = &Value::Nil;
-                if let Some(initializer) = initializer {
-                    value = self.evaluate(initializer)?;
-                }
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
 
                 self.environment.borrow_mut().define(name.lexeme.clone(), value);
                 Ok(())
             }
+            Stmt::While { condition, body } => {
+                while Interpreter::is_truthy(&self.evaluate(condition)?) {
+                    self.execute(body)?;
+                }
+                Ok(())
+            }
         }
     }
 }
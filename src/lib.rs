@@ -0,0 +1,498 @@
+//! A tree-walk interpreter for the Lox language from
+//! [Crafting Interpreters](https://craftinginterpreters.com/).
+//!
+//! The quickest way to run Lox code is through [`Lox`], which scans, parses,
+//! resolves and interprets source text against a long-lived interpreter:
+//!
+//! ```
+//! use rusty_lox::{Arenas, ErrorFormat, Lox};
+//!
+//! let arenas = Arenas::new();
+//! let mut lox = Lox::new(&arenas);
+//!
+//! let diagnostics = lox.run(b"var greeting = \"hello\";".to_vec());
+//! assert!(diagnostics.is_empty());
+//!
+//! let diagnostics = lox.run(b"print greeting -;".to_vec());
+//! assert!(diagnostics.had_error());
+//! for diagnostic in &diagnostics {
+//!     eprint!("{}", diagnostic.render(ErrorFormat::Rich, "<input>", lox.source()));
+//! }
+//! ```
+//!
+//! Nothing is reported to stderr by the library itself: every stage records
+//! what went wrong in [`Diagnostics`], and the host decides how to show it.
+//! The output of `print` goes to stdout unless the host passes another
+//! [`Output`] to [`Lox::with_output`].
+//!
+//! The individual stages ([`Scanner`], [`Parser`], [`Resolver`] and
+//! [`Interpreter`]) are public as well, for hosts that need to drive them
+//! separately.
+//!
+//! Programs run on the tree-walk [`Interpreter`] by default. Selecting
+//! [`Backend::Vm`] compiles them to bytecode for the faster [`Vm`] instead:
+//!
+//! ```
+//! use rusty_lox::{Arenas, Backend, Lox};
+//!
+//! let arenas = Arenas::new();
+//! let mut lox = Lox::new(&arenas).with_backend(Backend::Vm);
+//!
+//! let (diagnostics, value) = lox.run_repl(b"1 + 2".to_vec());
+//! assert!(diagnostics.is_empty());
+//! assert_eq!(value.as_deref(), Some("3"));
+//! ```
+//!
+//! Functions written in Rust can be exposed to Lox code on either backend.
+//! They trade [`Literal`]s, the values both backends share:
+//!
+//! ```
+//! use rusty_lox::{Arenas, Literal, Lox};
+//!
+//! let arenas = Arenas::new();
+//! let mut lox = Lox::new(&arenas);
+//!
+//! lox.define_native("double", 1, |arguments| match arguments[0] {
+//!     Literal::Number(number) => Ok(Literal::Number(number * 2.0)),
+//!     _ => Ok(Literal::Nil),
+//! });
+//!
+//! let (_, value) = lox.run_repl(b"double(21)".to_vec());
+//! assert_eq!(value.as_deref(), Some("42"));
+//! ```
+//!
+//! Both backends report a stack overflow once 1024 calls are in progress.
+//! The tree-walk interpreter recurses on the native stack for every Lox call,
+//! and grows it as needed on the way there, so this works on any thread the
+//! host runs it on.
+
+mod ast_printer;
+mod diagnostic;
+mod environment;
+mod error_code;
+mod expr;
+mod heap;
+mod interpreter;
+mod literal;
+mod lox_callable;
+mod lox_class;
+mod lox_function;
+mod lox_instance;
+mod native_function;
+mod output;
+mod parser;
+mod resolver;
+mod scanner;
+mod span;
+mod stmt;
+mod token;
+mod token_type;
+mod vm;
+
+use std::{rc::Rc, str::FromStr};
+
+use typed_arena::Arena;
+use vm::{bytecode, chunk::Function, compiler::Compiler, debug};
+
+pub use ast_printer::AstPrinter;
+pub use diagnostic::{Diagnostic, Diagnostics, ErrorFormat, Phase, Severity};
+pub use error_code::ErrorCode;
+pub use expr::Expr;
+pub use heap::{GcConfig, GcStats};
+pub use interpreter::{Interpreter, RuntimeError, StackFrame, Value};
+pub use literal::Literal;
+pub use output::Output;
+pub use parser::Parser;
+pub use resolver::Resolver;
+pub use scanner::{Scanner, KEYWORDS};
+pub use span::Span;
+pub use stmt::Stmt;
+pub use token::Token;
+pub use token_type::TokenType;
+pub use vm::{
+    bytecode::{LoadError, FORMAT_VERSION},
+    DebugConfig, Vm,
+};
+
+// Functions keep referring to the tokens and expressions they were parsed
+// from, so the syntax tree has to live as long as the interpreter does. The
+// arenas own all of it. Runtime values are reference counted instead, so
+// they are freed as soon as nothing refers to them.
+pub struct Arenas<'a> {
+    tokens: Arena<Vec<Token>>,
+    exprs: Arena<Expr<'a>>,
+}
+
+impl<'a> Arenas<'a> {
+    pub fn new() -> Arenas<'a> {
+        Arenas {
+            tokens: Arena::new(),
+            exprs: Arena::new(),
+        }
+    }
+}
+
+impl Default for Arenas<'_> {
+    fn default() -> Self {
+        Arenas::new()
+    }
+}
+
+// Which engine runs programs once they have been parsed and resolved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    // Walks the syntax tree directly.
+    #[default]
+    Tree,
+    // Compiles the syntax tree to bytecode and runs it on a stack VM.
+    Vm,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend {
+            "tree" => Ok(Backend::Tree),
+            "vm" => Ok(Backend::Vm),
+            _ => Err(format!("Unknown backend '{backend}'.")),
+        }
+    }
+}
+
+// A native defined with `Lox::define_native`, kept so that it can be defined
+// again on the fresh interpreter and VM that `reset` creates.
+type HostNative = Rc<dyn Fn(&[Literal]) -> Result<Literal, RuntimeError>>;
+
+// An interpreter session that source code can be fed into piece by piece,
+// keeping global state between runs the way the REPL needs.
+pub struct Lox<'a> {
+    // All source code run so far, which diagnostic spans index into.
+    source: String,
+    // The number of lines in `source`, kept up to date as code is added so
+    // that finding where the next piece starts doesn't rescan all of it.
+    lines: usize,
+    backend: Backend,
+    // The resolver records where local variables live in the interpreter,
+    // so it is needed to compile programs for either backend.
+    interpreter: Interpreter<'a>,
+    vm: Vm,
+    natives: Vec<(String, usize, HostNative)>,
+    arenas: &'a Arenas<'a>,
+}
+
+impl<'a> Lox<'a> {
+    pub fn new(arenas: &'a Arenas<'a>) -> Lox<'a> {
+        Lox::with_gc(arenas, GcConfig::default())
+    }
+
+    pub fn with_gc(arenas: &'a Arenas<'a>, config: GcConfig) -> Lox<'a> {
+        Lox {
+            source: String::new(),
+            lines: 0,
+            backend: Backend::default(),
+            interpreter: Interpreter::with_gc(config),
+            vm: Vm::with_gc(config),
+            natives: vec![],
+            arenas,
+        }
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Lox<'a> {
+        self.backend = backend;
+        self
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    // Turns on the VM's debugging output. It has no effect on the tree-walk
+    // backend.
+    pub fn with_debug(mut self, config: DebugConfig) -> Lox<'a> {
+        self.vm.set_debug(config);
+        self
+    }
+
+    // Where `print` statements write. Programs print to stdout unless the
+    // host sets somewhere else.
+    pub fn with_output(mut self, output: Output) -> Lox<'a> {
+        self.interpreter.set_output(Rc::clone(&output));
+        self.vm.set_output(output);
+        self
+    }
+
+    // Where debugging output and the collector's log go. They are thrown
+    // away unless the host sets somewhere to write them.
+    pub fn with_log(mut self, log: Output) -> Lox<'a> {
+        self.interpreter.set_log(Rc::clone(&log));
+        self.vm.set_log(log);
+        self
+    }
+
+    // Throws away every global variable, function and class defined so far,
+    // as if the session had just started.
+    pub fn reset(&mut self) {
+        self.source.clear();
+        self.lines = 0;
+
+        let output = self.interpreter.output();
+        let log = self.interpreter.log();
+        self.interpreter = Interpreter::with_gc(self.interpreter.gc_config());
+        self.interpreter.set_output(output);
+        self.interpreter.set_log(log);
+
+        let debug = self.vm.debug_config();
+        let output = self.vm.output();
+        let log = self.vm.log();
+        self.vm = Vm::with_gc(self.vm.gc_config());
+        self.vm.set_debug(debug);
+        self.vm.set_output(output);
+        self.vm.set_log(log);
+
+        for (name, arity, native) in self.natives.clone() {
+            self.install_native(&name, arity, &native);
+        }
+    }
+
+    // Exposes a function implemented in Rust to Lox code as a global, on
+    // both backends. It is passed and returns `Literal`s, the values the two
+    // backends have in common, and stays defined after `reset`.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Literal]) -> Result<Literal, RuntimeError> + 'static,
+    {
+        let native: HostNative = Rc::new(function);
+        self.install_native(name, arity, &native);
+        self.natives.push((name.to_string(), arity, native));
+    }
+
+    fn install_native(&mut self, name: &str, arity: usize, native: &HostNative) {
+        let function = Rc::clone(native);
+        self.interpreter.define_native(name, arity, move |arguments| {
+            let arguments = arguments
+                .iter()
+                .map(Literal::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            function(&arguments).map(Value::from)
+        });
+
+        let function = Rc::clone(native);
+        self.vm.define_native(name, arity, move |arguments| {
+            let arguments = arguments
+                .iter()
+                .map(Literal::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            function(&arguments).map(vm::value::Value::from)
+        });
+    }
+
+    // The text to quote when rendering diagnostics returned by `run`.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn interpreter(&mut self) -> &mut Interpreter<'a> {
+        &mut self.interpreter
+    }
+
+    pub fn vm(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    pub fn run(&mut self, source: Vec<u8>) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+
+        if let Some(statements) = self.compile(source, false, &mut diagnostics) {
+            let result = match self.backend {
+                Backend::Tree => self.interpreter.interpret(statements),
+                Backend::Vm => self.run_vm(&statements, &mut diagnostics),
+            };
+
+            if let Err(error) = result {
+                diagnostics.push(error.into());
+            }
+        }
+
+        diagnostics
+    }
+
+    // Runs a line typed at the REPL. A lone expression there may leave out
+    // its semicolon, and its value is returned the way `print` would show
+    // it rather than being thrown away.
+    pub fn run_repl(&mut self, source: Vec<u8>) -> (Diagnostics, Option<String>) {
+        let mut diagnostics = Diagnostics::new();
+
+        let Some(statements) = self.compile(source, true, &mut diagnostics) else {
+            return (diagnostics, None);
+        };
+
+        if let [Stmt::Expression(expression)] = statements.as_slice() {
+            let result = match self.backend {
+                Backend::Tree => self
+                    .interpreter
+                    .interpret_expression(expression)
+                    .map(|value| Some(Interpreter::stringify(&value))),
+                Backend::Vm => self.evaluate_vm(expression, &mut diagnostics),
+            };
+
+            return match result {
+                Ok(value) => (diagnostics, value),
+                Err(error) => {
+                    diagnostics.push(error.into());
+                    (diagnostics, None)
+                }
+            };
+        }
+
+        let result = match self.backend {
+            Backend::Tree => self.interpreter.interpret(statements),
+            Backend::Vm => self.run_vm(&statements, &mut diagnostics),
+        };
+
+        if let Err(error) = result {
+            diagnostics.push(error.into());
+        }
+
+        (diagnostics, None)
+    }
+
+    // Compiles source code for the VM and serializes it in the `.loxc`
+    // format, without running it.
+    pub fn compile_bytecode(&mut self, source: Vec<u8>) -> (Diagnostics, Option<Vec<u8>>) {
+        let mut diagnostics = Diagnostics::new();
+        let start = self.end_of_source();
+
+        let Some(statements) = self.compile(source, false, &mut diagnostics) else {
+            return (diagnostics, None);
+        };
+
+        let Some(script) = Compiler::new(&mut diagnostics).compile(&statements) else {
+            return (diagnostics, None);
+        };
+
+        self.disassemble(&script);
+        let bytes = bytecode::encode(&self.source[start.0..], &script, start);
+
+        (diagnostics, Some(bytes))
+    }
+
+    // Runs a script serialized by `compile_bytecode` on the VM, whichever
+    // backend is selected. Its source is added to the session so that
+    // runtime errors can still quote it.
+    pub fn run_bytecode(&mut self, bytes: &[u8]) -> Result<Diagnostics, LoadError> {
+        let start = self.end_of_source();
+        let (source, script) = bytecode::decode(bytes, start)?;
+        self.push_source(&source);
+
+        self.disassemble(&script);
+
+        let mut diagnostics = Diagnostics::new();
+        if let Err(error) = self.vm.interpret(script) {
+            diagnostics.push(error.into());
+        }
+
+        Ok(diagnostics)
+    }
+
+    // Compiles the statements to bytecode and runs them on the VM. Programs
+    // that hit one of the VM's limits are reported in `diagnostics` and not
+    // run at all.
+    fn run_vm(
+        &mut self,
+        statements: &[Stmt<'a>],
+        diagnostics: &mut Diagnostics,
+    ) -> Result<(), RuntimeError> {
+        match Compiler::new(diagnostics).compile(statements) {
+            Some(script) => {
+                self.disassemble(&script);
+                self.vm.interpret(script).map(|_| ())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn evaluate_vm(
+        &mut self,
+        expression: &Expr<'a>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Option<String>, RuntimeError> {
+        match Compiler::new(diagnostics).compile_expression(expression) {
+            Some(script) => {
+                self.disassemble(&script);
+                self.vm
+                    .interpret(script)
+                    .map(|value| Some(value.to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn disassemble(&self, script: &Function) {
+        if self.vm.debug_config().disassemble {
+            let listing = debug::disassemble(script);
+            let _ = self.vm.log().borrow_mut().write_all(listing.as_bytes());
+        }
+    }
+
+    // Scans, parses and resolves the source, returning the statements to run
+    // unless there were errors.
+    fn compile(
+        &mut self,
+        source: Vec<u8>,
+        repl: bool,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Vec<Stmt<'a>>> {
+        let (offset, line) = self.add_source(&source);
+        let scanner = Scanner::new_at(source, offset, line);
+
+        let tokens = self.arenas.tokens.alloc(scanner.scan_tokens(diagnostics));
+
+        let mut parser = Parser::new(diagnostics, tokens);
+        if repl {
+            parser = parser.for_repl();
+        }
+        let statements = parser.parse(&self.arenas.exprs);
+
+        // Stop if there was a syntax error.
+        if diagnostics.had_error() {
+            return None;
+        }
+
+        let statements = statements.unwrap();
+
+        let mut resolver = Resolver::new(diagnostics, &mut self.interpreter);
+        resolver.resolve(&statements);
+
+        // Stop if there was a resolution error.
+        if diagnostics.had_error() {
+            return None;
+        }
+
+        Some(statements)
+    }
+
+    // Appends more source code to quote in diagnostics, returning the byte
+    // offset and line number it starts at.
+    fn add_source(&mut self, source: &[u8]) -> (usize, usize) {
+        let start = self.end_of_source();
+        self.push_source(&String::from_utf8_lossy(source));
+
+        start
+    }
+
+    fn push_source(&mut self, source: &str) {
+        self.lines += source.matches('\n').count();
+        self.source.push_str(source);
+    }
+
+    // Starts a new line for the next piece of source code, returning the
+    // byte offset and line number it will start at.
+    fn end_of_source(&mut self) -> (usize, usize) {
+        if !self.source.is_empty() && !self.source.ends_with('\n') {
+            self.push_source("\n");
+        }
+
+        (self.source.len(), self.lines + 1)
+    }
+}
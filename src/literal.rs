@@ -1,6 +1,9 @@
 use std::fmt::Display;
 
-#[derive(PartialEq)]
+// A value written directly in the source code. These are also the values
+// that natives registered with `Lox::define_native` trade with Lox code,
+// since both backends represent them the same way.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Bool(bool),
     Number(f64),
@@ -17,7 +20,7 @@ impl Display for Literal {
             // doesn't do this, so we use that instead.
             Literal::Number(num) => write!(f, "{num:?}"),
             Literal::String(string) => string.fmt(f),
-            Literal::Nil => write!(f, "null"),
+            Literal::Nil => write!(f, "nil"),
         }
     }
 }
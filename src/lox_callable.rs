@@ -0,0 +1,11 @@
+use crate::interpreter::{Interpreter, RuntimeError, Value};
+
+pub trait LoxCallable<'a> {
+    fn arity(&self) -> usize;
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter<'a>,
+        arguments: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError>;
+}
@@ -0,0 +1,100 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{
+    heap::{Trace, Tracer},
+    interpreter::{Interpreter, RuntimeError, Value},
+    lox_callable::LoxCallable,
+    lox_function::LoxFunction,
+    lox_instance::LoxInstance,
+};
+
+pub struct LoxClass<'a> {
+    pub name: String,
+    superclass: Option<Rc<LoxClass<'a>>>,
+    methods: HashMap<String, Rc<LoxFunction<'a>>>,
+}
+
+impl<'a> LoxClass<'a> {
+    pub(crate) fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass<'a>>>,
+        methods: HashMap<String, Rc<LoxFunction<'a>>>,
+    ) -> LoxClass<'a> {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    // Methods are inherited, so look through the superclass chain when this
+    // class doesn't define the method itself.
+    pub(crate) fn find_method(&self, name: &str) -> Option<&Rc<LoxFunction<'a>>> {
+        self.methods
+            .get(name)
+            .or_else(|| self.superclass.as_ref()?.find_method(name))
+    }
+
+    // The names of the methods defined on this class and its superclasses.
+    pub(crate) fn method_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.methods.keys().cloned().collect();
+
+        if let Some(superclass) = &self.superclass {
+            names.extend(superclass.method_names());
+        }
+
+        names
+    }
+}
+
+// Calling a class creates an instance that points back at the class, which
+// needs the shared handle rather than a plain reference to the class.
+impl<'a> LoxCallable<'a> for Rc<LoxClass<'a>> {
+    fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter<'a>,
+        arguments: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let instance = LoxInstance::new(self.clone());
+        let instance = Rc::new(RefCell::new(instance));
+        interpreter.track(&instance);
+        let instance = Value::Instance(instance);
+
+        if let Some(initializer) = self.find_method("init") {
+            initializer
+                .bind(instance.clone(), interpreter)
+                .call(interpreter, arguments)?;
+        }
+
+        Ok(instance)
+    }
+}
+
+impl Display for LoxClass<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl<'a> Trace<'a> for LoxClass<'a> {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(superclass) = &self.superclass {
+            tracer.object(superclass);
+        }
+
+        for method in self.methods.values() {
+            tracer.object(method);
+        }
+    }
+
+    // Classes only refer to their superclass and methods, so the cycles they
+    // are part of are broken by clearing environments and instances.
+    fn clear(&self) {}
+}
@@ -0,0 +1,105 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    heap::{Trace, Tracer},
+    interpreter::{Interpreter, RuntimeError, Unwind, Value},
+    lox_callable::LoxCallable,
+    stmt::Function,
+};
+
+pub struct LoxFunction<'a> {
+    declaration: Rc<Function<'a>>,
+    // The environment the function was declared in. Holding on to it keeps
+    // every variable the function body can see alive between calls.
+    closure: Rc<RefCell<Environment<'a>>>,
+    is_initializer: bool,
+}
+
+impl<'a> LoxFunction<'a> {
+    pub(crate) fn new(
+        declaration: Rc<Function<'a>>,
+        closure: Rc<RefCell<Environment<'a>>>,
+        is_initializer: bool,
+    ) -> LoxFunction<'a> {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.declaration.name.lexeme
+    }
+
+    // Creates a copy of this method whose closure defines `this` as the given
+    // instance.
+    pub(crate) fn bind(&self, instance: Value<'a>, interpreter: &mut Interpreter<'a>) -> Rc<LoxFunction<'a>> {
+        let environment = Environment::new_with_enclosing(&self.closure);
+        interpreter.track(&environment);
+        environment
+            .borrow_mut()
+            .define("this".to_string(), instance);
+
+        let method = Rc::new(LoxFunction::new(
+            self.declaration.clone(),
+            environment,
+            self.is_initializer,
+        ));
+        interpreter.track(&method);
+        method
+    }
+
+    // An initializer always hands back the instance it was bound to.
+    fn this(&self) -> Value<'a> {
+        self.closure
+            .borrow()
+            .get_at(0, "this")
+            .expect("initializer should be bound to an instance")
+    }
+}
+
+impl<'a> LoxCallable<'a> for LoxFunction<'a> {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter<'a>,
+        arguments: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let environment = Environment::new_with_enclosing(&self.closure);
+        interpreter.track(&environment);
+
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment
+                .borrow_mut()
+                .define(param.lexeme.clone(), argument);
+        }
+
+        match interpreter.execute_block(&self.declaration.body, environment) {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self.this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+}
+
+impl Display for LoxFunction<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+impl<'a> Trace<'a> for LoxFunction<'a> {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.object(&self.closure);
+    }
+
+    // Every cycle through a function also runs through the environment it
+    // closes over, and clearing that is enough to break it.
+    fn clear(&self) {}
+}
@@ -0,0 +1,92 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{
+    error_code::ErrorCode,
+    heap::{Trace, Tracer},
+    interpreter::{Interpreter, RuntimeError, Value},
+    lox_class::LoxClass,
+    token::Token,
+};
+
+pub struct LoxInstance<'a> {
+    class: Rc<LoxClass<'a>>,
+    fields: HashMap<String, Value<'a>>,
+}
+
+impl<'a> LoxInstance<'a> {
+    pub(crate) fn new(class: Rc<LoxClass<'a>>) -> LoxInstance<'a> {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // Fields shadow methods. Methods are bound to `this`, the value holding
+    // this instance, so they can be called after being pulled off of it.
+    pub(crate) fn get(
+        &self,
+        name: &Token,
+        this: &Value<'a>,
+        interpreter: &mut Interpreter<'a>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        if let Some(value) = self.fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        if let Some(method) = self.class.find_method(&name.lexeme) {
+            let method = method.bind(this.clone(), interpreter);
+            return Ok(Value::Function(method));
+        }
+
+        Err(RuntimeError::new(
+            ErrorCode::UndefinedProperty,
+            name,
+            format!("Undefined property '{}'.", name.lexeme),
+        ))
+    }
+
+    pub(crate) fn set(&mut self, name: &Token, value: Value<'a>) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+
+    pub fn field(&self, name: &str) -> Option<Value<'a>> {
+        self.fields.get(name).cloned()
+    }
+
+    // Every field and method that can be accessed on the instance, sorted
+    // and without duplicates.
+    pub fn property_names(&self) -> Vec<String> {
+        let mut names = self.class.method_names();
+        names.extend(self.fields.keys().cloned());
+
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+impl Display for LoxInstance<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl<'a> Trace<'a> for RefCell<LoxInstance<'a>> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(instance) = self.try_borrow() else {
+            tracer.opaque();
+            return;
+        };
+
+        tracer.object(&instance.class);
+        for value in instance.fields.values() {
+            tracer.value(value);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut instance) = self.try_borrow_mut() {
+            instance.fields.clear();
+        }
+    }
+}
@@ -1,98 +1,134 @@
-mod expr;
-mod literal;
-mod parser;
-mod scanner;
-mod token;
-mod token_type;
-mod interpreter;
-mod stmt;
-mod environment;
-
-use std::{
-    cmp::Ordering,
-    env, fs,
-    io::{self, Write},
-    process::exit, cell::RefCell,
-};
-
-use interpreter::{RuntimeError, Interpreter};
-use parser::Parser;
-use token::Token;
-use token_type::TokenType;
-use typed_arena::Arena;
-
-use crate::scanner::Scanner;
-
-pub struct Context {
-    had_error: bool,
-    had_runtime_error: bool
-}
+mod repl;
 
-impl Context {
-    fn new() -> Context {
-        Context {
-            had_error: false,
-            had_runtime_error: false
-        }
-    }
+use std::{cell::RefCell, env, fs, io, path::Path, process::exit, rc::Rc};
 
-    fn error(&mut self, line: usize, message: &str) {
-        self.report(line, "", message);
-    }
+use rusty_lox::{Arenas, Backend, DebugConfig, Diagnostics, ErrorCode, ErrorFormat, GcConfig, Lox};
 
-    fn runtime_error(&mut self, error: RuntimeError) {
-        println!("{}\n[line {}]", error.message, error.token_line);
-        self.had_runtime_error = true;
-    }
+const USAGE: &str = "Usage: rusty-lox [--error-format=rich|jlox|json] [--backend=tree|vm] [--disassemble] [--trace-exec] [--gc-stress] [--gc-log] [script]\n       rusty-lox compile <script> [-o <output>]\n       rusty-lox --explain <code>";
 
-    fn report(&mut self, line: usize, r#where: &str, message: &str) {
-        eprintln!("[line {line}] Error{where}: {message}");
-        self.had_error = true;
-    }
+fn main() {
+    let mut error_format = ErrorFormat::Rich;
+    let mut backend = Backend::default();
+    let mut gc = GcConfig::default();
+    let mut debug = DebugConfig::default();
+    let mut scripts = vec![];
+    let mut output = None;
+
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--explain" {
+            match args.next() {
+                Some(code) => explain(&code),
+                None => println!("{USAGE}"),
+            }
 
-    fn error_with_token(&mut self, token: &Token, message: &str) {
-        if token.r#type == TokenType::Eof {
-            self.report(token.line, " at end", message);
+            return;
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            error_format = match format.parse() {
+                Ok(format) => format,
+                Err(message) => {
+                    eprintln!("{message}");
+                    println!("{USAGE}");
+                    exit(64);
+                }
+            };
+        } else if let Some(name) = arg.strip_prefix("--backend=") {
+            backend = match name.parse() {
+                Ok(backend) => backend,
+                Err(message) => {
+                    eprintln!("{message}");
+                    println!("{USAGE}");
+                    exit(64);
+                }
+            };
+        } else if arg == "--disassemble" {
+            debug.disassemble = true;
+        } else if arg == "--trace-exec" {
+            debug.trace_exec = true;
+        } else if arg == "-o" {
+            match args.next() {
+                Some(path) => output = Some(path),
+                None => {
+                    println!("{USAGE}");
+                    exit(64);
+                }
+            }
+        } else if arg == "--gc-stress" {
+            gc.stress = true;
+        } else if arg == "--gc-log" {
+            gc.log = true;
         } else {
-            self.report(token.line, &format!(" at '{}'", token.lexeme), message);
+            scripts.push(arg);
         }
     }
-}
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
 
-    let context = RefCell::new(Context::new());
+    // Compiling and running `.loxc` files always uses the VM.
+    let uses_vm = backend == Backend::Vm
+        || matches!(scripts.as_slice(), [command, _] if command == "compile")
+        || matches!(scripts.as_slice(), [script] if is_bytecode(script));
 
-    let arena = Arena::new();
-    let mut interpreter = Interpreter::new(&arena);
+    if (debug.disassemble || debug.trace_exec) && !uses_vm {
+        eprintln!("--disassemble and --trace-exec need --backend=vm.");
+        println!("{USAGE}");
+        exit(64);
+    }
 
-    match args.len().cmp(&2) {
-        Ordering::Greater => {
-            println!("Usage: rusty-lox [script]");
+    let arenas = Arenas::new();
+    let mut lox = Lox::with_gc(&arenas, gc)
+        .with_backend(backend)
+        .with_debug(debug)
+        .with_log(Rc::new(RefCell::new(io::stderr())));
+
+    match (scripts.as_slice(), output) {
+        ([], None) => repl::run(&mut lox, error_format),
+        ([command], _) if command == "compile" => println!("{USAGE}"),
+        ([command, script], output) if command == "compile" => {
+            compile_file(&mut lox, error_format, script, output)
         }
-        Ordering::Equal => {
-            run_file(context, &mut interpreter, &args[1]);
+        ([script], None) if is_bytecode(script) => {
+            run_bytecode_file(&mut lox, error_format, script)
         }
-        Ordering::Less => {
-            run_prompt(context, &mut interpreter);
+        ([script], None) => run_file(&mut lox, error_format, script),
+        _ => println!("{USAGE}"),
+    }
+}
+
+fn is_bytecode(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension == "loxc")
+}
+
+fn explain(code: &str) {
+    match code.parse::<ErrorCode>() {
+        Ok(code) => println!("{}", code.explanation()),
+        Err(message) => {
+            eprintln!("{message}");
+            exit(64);
         }
     }
 }
 
-fn run_file(context: RefCell<Context>, interpreter: &mut Interpreter, path: &str) {
-    let content = fs::read(&path);
+fn report(lox: &Lox, diagnostics: &Diagnostics, format: ErrorFormat, path: &str) {
+    for diagnostic in diagnostics {
+        eprint!("{}", diagnostic.render(format, path, lox.source()));
+    }
+}
+
+fn run_file(lox: &mut Lox, format: ErrorFormat, path: &str) {
+    let content = fs::read(path);
 
     match content {
         Ok(content) => {
-            run(&context, interpreter, content);
+            let diagnostics = lox.run(content);
+            report(lox, &diagnostics, format, path);
 
-            let context = context.borrow();
-            if context.had_error {
+            if diagnostics.had_error() {
                 exit(65);
             }
 
-            if context.had_runtime_error {
+            if diagnostics.had_runtime_error() {
                 exit(70);
             }
         }
@@ -104,39 +140,52 @@ fn run_file(context: RefCell<Context>, interpreter: &mut Interpreter, path: &str
     };
 }
 
-fn run_prompt(context: RefCell<Context>, interpreter: &mut Interpreter) {
-    loop {
-        print!("> ");
-        io::stdout().flush().unwrap();
-
-        let mut line = String::new();
+// Compiles a script to bytecode and writes it to `output`, or next to the
+// script with a `.loxc` extension, so that it can be run without parsing it
+// again.
+fn compile_file(lox: &mut Lox, format: ErrorFormat, path: &str, output: Option<String>) {
+    let Ok(content) = fs::read(path) else {
+        eprintln!("Error reading file!");
+        exit(70);
+    };
 
-        io::stdin()
-            .read_line(&mut line)
-            .expect("Failed to read line");
+    let (diagnostics, bytes) = lox.compile_bytecode(content);
+    report(lox, &diagnostics, format, path);
 
-        line.truncate(line.len() - 1);
+    let Some(bytes) = bytes else {
+        exit(65);
+    };
 
-        run(&context, interpreter, line.into_bytes());
+    let output = output.unwrap_or_else(|| {
+        Path::new(path)
+            .with_extension("loxc")
+            .to_string_lossy()
+            .into_owned()
+    });
 
-        context.borrow_mut().had_error = false;
+    if let Err(error) = fs::write(&output, bytes) {
+        eprintln!("Could not write {output}: {error}");
+        exit(74);
     }
 }
 
-fn run(context: &RefCell<Context>, interpreter: &mut Interpreter, source: Vec<u8>) {
-    let scanner = Scanner::new(source);
-
-    let tokens = scanner.scan_tokens(context);
+fn run_bytecode_file(lox: &mut Lox, format: ErrorFormat, path: &str) {
+    let Ok(content) = fs::read(path) else {
+        eprintln!("Error reading file!");
+        exit(70);
+    };
 
-    let arena = Arena::new();
-    let mut parser = Parser::new(context, tokens);
-    let statements = parser.parse(&arena);
+    match lox.run_bytecode(&content) {
+        Ok(diagnostics) => {
+            report(lox, &diagnostics, format, path);
 
-    if context.borrow().had_error {
-        return;
+            if diagnostics.had_runtime_error() {
+                exit(70);
+            }
+        }
+        Err(error) => {
+            eprintln!("Could not run {path}: {error}.");
+            exit(65);
+        }
     }
-
-    let expression = statements.unwrap();
-
-    interpreter.interpret(context, expression);
 }
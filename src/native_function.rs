@@ -0,0 +1,43 @@
+use std::fmt::Display;
+
+use crate::{
+    interpreter::{Interpreter, RuntimeError, Value},
+    lox_callable::LoxCallable,
+};
+
+// Natives work for values of any lifetime, so the same closure can be
+// registered with interpreters running unrelated syntax trees.
+pub type NativeFn = dyn for<'v> Fn(&[Value<'v>]) -> Result<Value<'v>, RuntimeError>;
+
+// A function implemented in Rust by the host and exposed to Lox code as a
+// global.
+pub struct NativeFunction {
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub(crate) fn new(arity: usize, function: Box<NativeFn>) -> NativeFunction {
+        NativeFunction { arity, function }
+    }
+}
+
+impl<'a> LoxCallable<'a> for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter<'a>,
+        arguments: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        (self.function)(&arguments)
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
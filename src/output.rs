@@ -0,0 +1,20 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+// Somewhere for the library to write text to, such as the output of `print`
+// or the VM's debugging output. It is shared so that the host can hand the same one to several
+// places and still read back what was written.
+pub type Output = Rc<RefCell<dyn Write>>;
+
+pub fn stdout() -> Output {
+    Rc::new(RefCell::new(io::stdout()))
+}
+
+// Throws everything away. Debugging output goes here until the host asks for
+// it, so that the library never writes to the terminal on its own.
+pub fn discard() -> Output {
+    Rc::new(RefCell::new(io::sink()))
+}
@@ -1,13 +1,18 @@
-use std::cell::{RefCell, Cell};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
-use crate::{expr::Expr, literal::Literal, token::Token, token_type::TokenType, Context, stmt::Stmt};
+use crate::{expr::Expr, literal::Literal, token::Token, token_type::TokenType, diagnostic::Diagnostics, error_code::ErrorCode, stmt::{Function, Stmt}};
 
 use typed_arena::Arena;
 
-pub struct Parser<'a> {
-    context: &'a RefCell<Context>,
-    tokens: Vec<Token>,
+pub struct Parser<'d, 'a> {
+    diagnostics: RefCell<&'d mut Diagnostics>,
+    tokens: &'a [Token],
     current: Cell<usize>,
+    // Whether a trailing expression may leave out its semicolon.
+    repl: bool,
 }
 
 struct ParseError;
@@ -16,34 +21,59 @@ const FALSE_LITERAL: Literal = Literal::Bool(false);
 const TRUE_LITERAL: Literal = Literal::Bool(true);
 const NIL_LITERAL: Literal = Literal::Nil;
 
-impl<'a> Parser<'a> {
-    pub fn new(context: &RefCell<Context>, tokens: Vec<Token>) -> Parser {
+impl<'d, 'a> Parser<'d, 'a> {
+    pub fn new(diagnostics: &'d mut Diagnostics, tokens: &'a [Token]) -> Parser<'d, 'a> {
         Parser {
-            context,
+            diagnostics: RefCell::new(diagnostics),
             tokens,
             current: Cell::new(0),
+            repl: false,
         }
     }
 
-    pub fn parse(&'a mut self, arena: &'a Arena<Expr<'a>>) -> Option<Vec<Stmt>> {
+    // Parses input typed at the REPL, where `1 + 2` is accepted without a
+    // semicolon at the end.
+    pub fn for_repl(mut self) -> Parser<'d, 'a> {
+        self.repl = true;
+        self
+    }
+
+    pub fn parse(&mut self, arena: &'a Arena<Expr<'a>>) -> Option<Vec<Stmt<'a>>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            let statement = self.declaration(arena);
-
-            match statement {
-                Some(statement) => statements.push(statement),
-                None => ()
+            if let Some(statement) = self.declaration(arena) {
+                statements.push(statement);
             }
         }
 
         Some(statements)
     }
 
-    fn expression(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
+    fn expression(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
         self.assignment(arena)
     }
 
-    fn declaration(&'a self, arena: &'a Arena<Expr<'a>>) -> Option<Stmt<'_>> {
+    fn declaration(&self, arena: &'a Arena<Expr<'a>>) -> Option<Stmt<'a>> {
+        if self.r#match(&[TokenType::Class]) {
+            return match self.class_declaration(arena) {
+                Ok(statement) => Some(statement),
+                Err(_) => {
+                    self.synchronize();
+                    None
+                }
+            };
+        }
+
+        if self.r#match(&[TokenType::Fun]) {
+            return match self.function(arena, "function") {
+                Ok(function) => Some(Stmt::Function(Rc::new(function))),
+                Err(_) => {
+                    self.synchronize();
+                    None
+                }
+            };
+        }
+
         if self.r#match(&[TokenType::Var]) {
             return match self.var_declaration(arena) {
                 Ok(statement) => Some(statement),
@@ -63,19 +93,132 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn statement(&'a self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'_>, ParseError> {
+    fn class_declaration(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let mut superclass = None;
+        if self.r#match(&[TokenType::Less]) {
+            let name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(&*arena.alloc(Expr::Variable(name)));
+        }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(Rc::new(self.function(arena, "method")?));
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+        })
+    }
+
+    fn statement(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
+        if self.r#match(&[TokenType::For]) {
+            return self.for_statement(arena);
+        }
+
+        if self.r#match(&[TokenType::If]) {
+            return self.if_statement(arena);
+        }
+
         if self.r#match(&[TokenType::Print]) {
             return self.print_statement(arena);
         }
 
+        if self.r#match(&[TokenType::Return]) {
+            return self.return_statement(arena);
+        }
+
+        if self.r#match(&[TokenType::While]) {
+            return self.while_statement(arena);
+        }
+
         if self.r#match(&[TokenType::LeftBrace]) {
             return Ok(Stmt::Block { statements: self.block(arena)? });
         }
- 
+
         self.expression_statement(arena)
     }
 
-    fn print_statement(&'a self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'_>, ParseError> {
+    fn for_statement(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
+        let keyword = self.get_token_at_index(self.previous());
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.r#match(&[TokenType::Semicolon]) {
+            None
+        } else if self.r#match(&[TokenType::Var]) {
+            Some(self.var_declaration(arena)?)
+        } else {
+            Some(self.expression_statement(arena)?)
+        };
+
+        let mut condition = None;
+        if !self.check(TokenType::Semicolon) {
+            condition = Some(self.expression(arena)?);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let mut increment = None;
+        if !self.check(TokenType::RightParen) {
+            increment = Some(self.expression(arena)?);
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement(arena)?;
+
+        // There is no dedicated for loop in the syntax tree, so we desugar it
+        // into a while loop wrapped in a block that holds the initializer.
+        if let Some(increment) = increment {
+            body = Stmt::Block {
+                statements: vec![body, Stmt::Expression(increment)],
+            };
+        }
+
+        let condition = condition.unwrap_or_else(|| {
+            arena.alloc(Expr::Literal {
+                value: &TRUE_LITERAL,
+                span: keyword.span,
+            })
+        });
+        body = Stmt::While {
+            condition,
+            body: Box::new(body),
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                statements: vec![initializer, body],
+            };
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression(arena)?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement(arena)?);
+        let mut else_branch = None;
+        if self.r#match(&[TokenType::Else]) {
+            else_branch = Some(Box::new(self.statement(arena)?));
+        }
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn print_statement(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
         let value = self.expression(arena)?;
 
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
@@ -83,7 +226,28 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Print(value))
     }
 
-    fn var_declaration(&'a self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'_>, ParseError> {
+    fn return_statement(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
+        let keyword = self.get_token_at_index(self.previous());
+
+        let mut value = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(self.expression(arena)?);
+        }
+
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn while_statement(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression(arena)?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.statement(arena)?);
+
+        Ok(Stmt::While { condition, body })
+    }
+
+    fn var_declaration(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
         let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
 
         let mut initializer = None;
@@ -96,15 +260,50 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Var { name, initializer })
     }
 
-    fn expression_statement(&'a self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'_>, ParseError> {
+    fn expression_statement(&self, arena: &'a Arena<Expr<'a>>) -> Result<Stmt<'a>, ParseError> {
         let value = self.expression(arena)?;
 
-        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        if self.repl && self.is_at_end() {
+            return Ok(Stmt::Expression(value));
+        }
+
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
 
         Ok(Stmt::Expression(value))
     }
 
-    fn block(&'a self, arena: &'a Arena<Expr<'a>>) -> Result<Vec<Stmt<'_>>, ParseError> {
+    fn function(&self, arena: &'a Arena<Expr<'a>>, kind: &str) -> Result<Function<'a>, ParseError> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {kind} name."))?;
+
+        self.consume(TokenType::LeftParen, &format!("Expect '(' after {kind} name."))?;
+        let mut params = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= 255 {
+                    // Report the error but keep parsing, the parser isn't confused.
+                    self.error(
+                        ErrorCode::TooManyParameters,
+                        self.peek(),
+                        "Can't have more than 255 parameters.",
+                    );
+                }
+
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+
+                if !self.r#match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {kind} body."))?;
+        let body = self.block(arena)?;
+
+        Ok(Function { name, params, body })
+    }
+
+    fn block(&self, arena: &'a Arena<Expr<'a>>) -> Result<Vec<Stmt<'a>>, ParseError> {
         let mut statements = vec![];
 
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
@@ -117,8 +316,8 @@ impl<'a> Parser<'a> {
         Ok(statements)
     }
 
-    fn assignment(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
-        let expr = self.equality(arena)?;
+    fn assignment(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
+        let expr = self.or(arena)?;
 
         if self.r#match(&[TokenType::Equal]) {
           let equals = self.previous();
@@ -126,15 +325,49 @@ impl<'a> Parser<'a> {
 
           if let Expr::Variable(name) = expr {
             return Ok(arena.alloc(Expr::Assign{ name, value }));
+          } else if let Expr::Get { object, name } = expr {
+            return Ok(arena.alloc(Expr::Set { object, name, value }));
           }
     
-          return Err(self.error(self.get_token_at_index(equals), "Invalid assignment target."));
+          return Err(self.error(ErrorCode::InvalidAssignmentTarget, self.get_token_at_index(equals), "Invalid assignment target."));
         }
     
         Ok(expr)
     }
 
-    fn equality(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
+    fn or(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
+        let mut expr = self.and(arena)?;
+
+        while self.r#match(&[TokenType::Or]) {
+            let operator = self.previous();
+            let right = self.and(arena)?;
+            expr = arena.alloc(Expr::Logical {
+                left: expr,
+                operator: self.get_token_at_index(operator),
+                right,
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
+        let mut expr = self.equality(arena)?;
+
+        while self.r#match(&[TokenType::And]) {
+            let operator = self.previous();
+            let right = self.equality(arena)?;
+            expr = arena.alloc(Expr::Logical {
+                left: expr,
+                operator: self.get_token_at_index(operator),
+                right,
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
         let mut expr = self.comparison(arena)?;
 
         while self.r#match(&[TokenType::BangEqual, TokenType::EqualEqual]) {
@@ -150,7 +383,7 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn comparison(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
+    fn comparison(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
         let mut expr = self.term(arena)?;
 
         while self.r#match(&[
@@ -171,7 +404,7 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn term(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
+    fn term(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
         let mut expr = self.factor(arena)?;
 
         while self.r#match(&[TokenType::Minus, TokenType::Plus]) {
@@ -187,7 +420,7 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn factor(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
+    fn factor(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
         let mut expr = self.unary(arena)?;
 
         while self.r#match(&[TokenType::Slash, TokenType::Star]) {
@@ -203,7 +436,7 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn unary(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
+    fn unary(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
         if self.r#match(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous();
             let right = self.unary(arena)?;
@@ -213,22 +446,85 @@ impl<'a> Parser<'a> {
             }));
         }
 
-        self.primary(arena)
+        self.call(arena)
+    }
+
+    fn call(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
+        let mut expr = self.primary(arena)?;
+
+        loop {
+            if self.r#match(&[TokenType::LeftParen]) {
+                expr = self.finish_call(arena, expr)?;
+            } else if self.r#match(&[TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = arena.alloc(Expr::Get { object: expr, name });
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(
+        &self,
+        arena: &'a Arena<Expr<'a>>,
+        callee: &'a Expr<'a>,
+    ) -> Result<&'a Expr<'a>, ParseError> {
+        let mut arguments = vec![];
+
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= 255 {
+                    // Report the error but keep parsing, the parser isn't confused.
+                    self.error(
+                        ErrorCode::TooManyArguments,
+                        self.peek(),
+                        "Can't have more than 255 arguments.",
+                    );
+                }
+
+                arguments.push(self.expression(arena)?);
+
+                if !self.r#match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(arena.alloc(Expr::Call {
+            callee,
+            paren,
+            arguments,
+        }))
     }
 
-    fn primary(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr, ParseError> {
+    fn primary(&self, arena: &'a Arena<Expr<'a>>) -> Result<&'a Expr<'a>, ParseError> {
         if self.r#match(&[TokenType::False]) {
-            return Ok(arena.alloc(Expr::Literal(&FALSE_LITERAL)));
+            return Ok(self.literal(arena, &FALSE_LITERAL));
         }
         if self.r#match(&[TokenType::True]) {
-            return Ok(arena.alloc(Expr::Literal(&TRUE_LITERAL)));
+            return Ok(self.literal(arena, &TRUE_LITERAL));
         }
         if self.r#match(&[TokenType::Nil]) {
-            return Ok(arena.alloc(Expr::Literal(&NIL_LITERAL)));
+            return Ok(self.literal(arena, &NIL_LITERAL));
         }
 
         if self.r#match(&[TokenType::Number, TokenType::String]) {
-            return Ok(arena.alloc(Expr::Literal(&self.get_token_at_index(self.previous()).literal)));
+            return Ok(self.literal(arena, &self.get_token_at_index(self.previous()).literal));
+        }
+
+        if self.r#match(&[TokenType::Super]) {
+            let keyword = self.get_token_at_index(self.previous());
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(arena.alloc(Expr::Super { keyword, method }));
+        }
+
+        if self.r#match(&[TokenType::This]) {
+            return Ok(arena.alloc(Expr::This(self.get_token_at_index(self.previous()))));
         }
 
         if self.r#match(&[TokenType::Identifier]) {
@@ -236,14 +532,24 @@ impl<'a> Parser<'a> {
         }
 
         if self.r#match(&[TokenType::LeftParen]) {
+            let left_paren = self.get_token_at_index(self.previous());
             let expr = self.expression(arena)?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            let right_paren = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
             return Ok(arena.alloc(Expr::Grouping {
                 expression: expr,
+                span: left_paren.span.to(right_paren.span),
             }));
         }
 
-        Err(self.error(self.peek(), "Expect expression."))
+        Err(self.error(ErrorCode::ExpectExpression, self.peek(), "Expect expression."))
+    }
+
+    // Allocates a literal expression for the token that was just consumed.
+    fn literal(&self, arena: &'a Arena<Expr<'a>>, value: &'a Literal) -> &'a Expr<'a> {
+        arena.alloc(Expr::Literal {
+            value,
+            span: self.get_token_at_index(self.previous()).span,
+        })
     }
 
     fn r#match(&self, types: &[TokenType]) -> bool {
@@ -257,16 +563,26 @@ impl<'a> Parser<'a> {
         false
     }
 
-    fn consume(&self, r#type: TokenType, message: &str) -> Result<&Token, ParseError> {
+    fn consume(&self, r#type: TokenType, message: &str) -> Result<&'a Token, ParseError> {
         if self.check(r#type) {
             return Ok(self.advance());
         }
 
-        Err(self.error(self.peek(), message))
+        let code = match r#type {
+            TokenType::Semicolon => ErrorCode::ExpectSemicolon,
+            TokenType::LeftParen | TokenType::RightParen => ErrorCode::ExpectParen,
+            TokenType::LeftBrace | TokenType::RightBrace => ErrorCode::ExpectBrace,
+            TokenType::Dot => ErrorCode::ExpectDot,
+            _ => ErrorCode::ExpectName,
+        };
+
+        Err(self.error(code, self.peek(), message))
     }
 
-    fn error(&self, token: &Token, message: &str) -> ParseError {
-        self.context.borrow_mut().error_with_token(token, message);
+    fn error(&self, code: ErrorCode, token: &Token, message: &str) -> ParseError {
+        self.diagnostics
+            .borrow_mut()
+            .error_at_token(code, token, message);
         ParseError
     }
 
@@ -304,7 +620,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn advance(&self) -> &Token {
+    fn advance(&self) -> &'a Token {
         if !self.is_at_end() {
             self.current.set(self.current.get() + 1);
         }
@@ -316,7 +632,7 @@ impl<'a> Parser<'a> {
         self.peek().r#type == TokenType::Eof
     }
 
-    fn peek(&self) -> &Token {
+    fn peek(&self) -> &'a Token {
         &self.tokens[self.current.get()]
     }
 
@@ -324,7 +640,7 @@ impl<'a> Parser<'a> {
         self.current.get() - 1
     }
 
-    fn get_token_at_index(&self, index: usize) -> &Token {
+    fn get_token_at_index(&self, index: usize) -> &'a Token {
         &self.tokens[index]
     }
 }
@@ -0,0 +1,284 @@
+mod completion;
+
+use std::{env, fs, path::PathBuf, time::Instant};
+
+use rustyline::{
+    error::ReadlineError, history::DefaultHistory, CompletionType, Config, Editor,
+};
+use rusty_lox::{
+    AstPrinter, Backend, Diagnostics, ErrorCode, ErrorFormat, Interpreter, Lox, Parser, Scanner,
+    TokenType,
+};
+use typed_arena::Arena;
+
+use crate::report;
+
+use self::completion::LoxHelper;
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".rusty_lox_history";
+
+const COMMANDS: [&str; 7] = [":env", ":load", ":tokens", ":ast", ":reset", ":time", ":help"];
+
+const HELP: &str = "\
+:env            List the variables defined in the session
+:load <file>    Run a file in the session
+:tokens <code>  Show the tokens the scanner produces for some code
+:ast <code>     Show the syntax tree the parser produces for some code
+:reset          Forget everything defined so far
+:time           Toggle showing how long each evaluation takes
+:help           Show this list";
+
+type LoxEditor<'a> = Editor<LoxHelper<'a>, DefaultHistory>;
+
+struct Repl<'l, 'a> {
+    lox: &'l mut Lox<'a>,
+    format: ErrorFormat,
+    timing: bool,
+}
+
+pub fn run(lox: &mut Lox, format: ErrorFormat) {
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+
+    let mut editor: LoxEditor = match Editor::with_config(config) {
+        Ok(editor) => editor,
+        Err(error) => {
+            eprintln!("Could not start the REPL: {error}");
+            return;
+        }
+    };
+    editor.set_helper(Some(LoxHelper::new()));
+
+    // There is no history yet the first time the REPL runs, so failing to
+    // load it is expected.
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+
+    let mut repl = Repl {
+        lox,
+        format,
+        timing: false,
+    };
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            match repl.lox.backend() {
+                Backend::Tree => helper.update(repl.lox.interpreter().bindings()),
+                Backend::Vm => {
+                    let globals = repl.lox.vm().globals();
+                    helper.update_names(globals.into_iter().map(|(name, _)| name).collect());
+                }
+            }
+        }
+
+        let Some(source) = read_input(&mut editor) else {
+            break;
+        };
+
+        if source.trim().is_empty() {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(source.trim_end());
+
+        match source.trim().strip_prefix(':') {
+            Some(command) => repl.command(command),
+            None => repl.evaluate(source),
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(error) = editor.save_history(path) {
+            eprintln!("Could not save history to {}: {error}", path.display());
+        }
+    }
+}
+
+impl Repl<'_, '_> {
+    fn evaluate(&mut self, source: String) {
+        let start = Instant::now();
+        let (diagnostics, value) = self.lox.run_repl(source.into_bytes());
+        let elapsed = start.elapsed();
+
+        report(self.lox, &diagnostics, self.format, "<repl>");
+
+        if let Some(value) = value {
+            println!("{value}");
+        }
+
+        if self.timing {
+            println!("took {elapsed:.2?}");
+        }
+    }
+
+    fn command(&mut self, input: &str) {
+        let (name, argument) = match input.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (input, ""),
+        };
+
+        match name {
+            "env" => self.env(),
+            "load" if argument.is_empty() => eprintln!("Usage: :load <file>"),
+            "load" => self.load(argument),
+            "tokens" if argument.is_empty() => eprintln!("Usage: :tokens <code>"),
+            "tokens" => self.tokens(argument),
+            "ast" if argument.is_empty() => eprintln!("Usage: :ast <code>"),
+            "ast" => self.ast(argument),
+            "reset" => {
+                self.lox.reset();
+                println!("Session reset.");
+            }
+            "time" => {
+                self.timing = !self.timing;
+                println!("Timing {}.", if self.timing { "on" } else { "off" });
+            }
+            "help" => println!("{HELP}"),
+            _ => eprintln!("Unknown command ':{name}'. Type :help for a list of commands."),
+        }
+    }
+
+    fn env(&mut self) {
+        match self.lox.backend() {
+            Backend::Tree => {
+                for (name, value) in self.lox.interpreter().bindings() {
+                    println!("{name} = {}", Interpreter::stringify(&value));
+                }
+            }
+            Backend::Vm => {
+                for (name, value) in self.lox.vm().globals() {
+                    println!("{name} = {value}");
+                }
+            }
+        }
+    }
+
+    fn load(&mut self, path: &str) {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(error) => {
+                eprintln!("Could not read {path}: {error}");
+                return;
+            }
+        };
+
+        let start = Instant::now();
+        let diagnostics = self.lox.run(content);
+        let elapsed = start.elapsed();
+
+        report(self.lox, &diagnostics, self.format, path);
+
+        if self.timing {
+            println!("took {elapsed:.2?}");
+        }
+    }
+
+    fn tokens(&self, code: &str) {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(code.as_bytes().to_vec()).scan_tokens(&mut diagnostics);
+
+        for token in &tokens {
+            println!("{}:{} {token}", token.span.line, token.span.column);
+        }
+
+        self.report_snippet(&diagnostics, code);
+    }
+
+    fn ast(&self, code: &str) {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(code.as_bytes().to_vec()).scan_tokens(&mut diagnostics);
+
+        let arena = Arena::new();
+        let statements = Parser::new(&mut diagnostics, &tokens)
+            .for_repl()
+            .parse(&arena);
+
+        if diagnostics.had_error() {
+            self.report_snippet(&diagnostics, code);
+            return;
+        }
+
+        if let Some(statements) = statements {
+            println!("{}", AstPrinter::new().print(&statements));
+        }
+    }
+
+    // Reports diagnostics for code that was only inspected rather than run,
+    // so their spans index into that code instead of the session's source.
+    fn report_snippet(&self, diagnostics: &Diagnostics, code: &str) {
+        for diagnostic in diagnostics {
+            eprint!("{}", diagnostic.render(self.format, "<input>", code));
+        }
+    }
+}
+
+// Reads lines until they form a complete piece of input, showing a
+// continuation prompt while brackets or a string are left open. Returns
+// `None` once the user presses Ctrl-D.
+fn read_input(editor: &mut LoxEditor) -> Option<String> {
+    let mut source = String::new();
+
+    loop {
+        let prompt = if source.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+
+        match editor.readline(prompt) {
+            Ok(line) => {
+                // No newline after the last line, so that an error at the
+                // end of the input points at the end of what was typed.
+                if !source.is_empty() {
+                    source.push('\n');
+                }
+                source.push_str(&line);
+
+                if !is_incomplete(&source) {
+                    return Some(source);
+                }
+            }
+            // Ctrl-C throws away whatever has been typed so far.
+            Err(ReadlineError::Interrupted) => source.clear(),
+            Err(ReadlineError::Eof) => return None,
+            Err(error) => {
+                eprintln!("{error}");
+                return None;
+            }
+        }
+    }
+}
+
+// Whether the source has more opening than closing brackets, or ends inside
+// a string, so that the next line should be read as part of it. Anything
+// else, including unbalanced closing brackets, is left for the parser to
+// report.
+fn is_incomplete(source: &str) -> bool {
+    let mut diagnostics = Diagnostics::new();
+    let tokens = Scanner::new(source.as_bytes().to_vec()).scan_tokens(&mut diagnostics);
+
+    let unterminated_string = diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.code == ErrorCode::UnterminatedString);
+
+    let mut depth = 0;
+    for token in &tokens {
+        match token.r#type {
+            TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+
+    unterminated_string || depth > 0
+}
+
+fn history_path() -> Option<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(HISTORY_FILE))
+}
@@ -0,0 +1,145 @@
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+use rusty_lox::{Value, KEYWORDS};
+
+use super::COMMANDS;
+
+// Completes keywords, REPL commands and the names of global variables, as
+// well as the fields and methods of instances after a `.`.
+pub struct LoxHelper<'a> {
+    // A snapshot of the session's global variables, refreshed before each
+    // prompt.
+    bindings: Vec<(String, Value<'a>)>,
+    globals: Vec<String>,
+}
+
+impl<'a> LoxHelper<'a> {
+    pub fn new() -> LoxHelper<'a> {
+        LoxHelper {
+            bindings: vec![],
+            globals: vec![],
+        }
+    }
+
+    pub fn update(&mut self, bindings: Vec<(String, Value<'a>)>) {
+        self.globals = bindings.iter().map(|(name, _)| name.clone()).collect();
+        self.bindings = bindings;
+    }
+
+    // The VM's values aren't inspected, so only the names of globals are
+    // completed when running on it.
+    pub fn update_names(&mut self, globals: Vec<String>) {
+        self.bindings.clear();
+        self.globals = globals;
+    }
+
+    fn names(&self) -> Vec<String> {
+        let keywords = KEYWORDS.iter().map(|(keyword, _)| keyword.to_string());
+        let globals = self.globals.iter().cloned();
+
+        keywords.chain(globals).collect()
+    }
+
+    // The properties of the instance that a chain of property accesses such
+    // as `a.b` evaluates to, without running any code.
+    fn properties(&self, chain: &str) -> Vec<String> {
+        let mut names = chain.split('.');
+
+        let Some(mut value) = names.next().and_then(|name| self.global(name)) else {
+            return vec![];
+        };
+
+        for name in names {
+            let Value::Instance(instance) = value else {
+                return vec![];
+            };
+
+            let Some(field) = instance.borrow().field(name) else {
+                return vec![];
+            };
+            value = field;
+        }
+
+        match value {
+            Value::Instance(instance) => instance.borrow().property_names(),
+            _ => vec![],
+        }
+    }
+
+    fn global(&self, name: &str) -> Option<Value<'a>> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+impl Completer for LoxHelper<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+
+        if before.starts_with(':') && !before.contains(char::is_whitespace) {
+            let commands = COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(before))
+                .map(|command| command.to_string())
+                .collect();
+
+            return Ok((0, commands));
+        }
+
+        let start = word_start(before);
+        let word = &before[start..];
+
+        let mut candidates = match before[..start].strip_suffix('.') {
+            Some(object) => self.properties(&object[word_start_with_dots(object)..]),
+            None => self.names(),
+        };
+
+        candidates.retain(|candidate| candidate.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for LoxHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper<'_> {}
+
+impl Validator for LoxHelper<'_> {}
+
+impl Helper for LoxHelper<'_> {}
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Where the identifier that the text ends with begins.
+fn word_start(text: &str) -> usize {
+    text.char_indices()
+        .rev()
+        .find(|(_, c)| !is_identifier(*c))
+        .map_or(0, |(index, c)| index + c.len_utf8())
+}
+
+// Where the chain of identifiers joined by dots that the text ends with
+// begins.
+fn word_start_with_dots(text: &str) -> usize {
+    text.char_indices()
+        .rev()
+        .find(|(_, c)| !is_identifier(*c) && *c != '.')
+        .map_or(0, |(index, c)| index + c.len_utf8())
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use crate::{
+    diagnostic::Diagnostics,
+    error_code::ErrorCode,
+    expr::{Expr, Visitor as ExprVisitor},
+    interpreter::Interpreter,
+    stmt::{Function, Stmt, Visitor as StmtVisitor},
+    token::Token,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver<'a, 'i> {
+    diagnostics: &'i mut Diagnostics,
+    interpreter: &'i mut Interpreter<'a>,
+    // Each scope maps a variable name to whether its initializer has
+    // finished resolving. Global variables are not tracked here.
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+}
+
+impl<'a, 'i> Resolver<'a, 'i> {
+    pub fn new(
+        diagnostics: &'i mut Diagnostics,
+        interpreter: &'i mut Interpreter<'a>,
+    ) -> Resolver<'a, 'i> {
+        Resolver {
+            diagnostics,
+            interpreter,
+            scopes: vec![],
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt<'a>]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt<'a>) {
+        self.visit_stmt(stmt);
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr<'a>) {
+        self.visit_expr(expr);
+    }
+
+    fn resolve_function(&mut self, function: &Function<'a>, r#type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = r#type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn error(&mut self, code: ErrorCode, token: &Token, message: &str) {
+        self.diagnostics.error_at_token(code, token, message);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if scope.contains_key(&name.lexeme) {
+            self.error(
+                ErrorCode::AlreadyDeclared,
+                name,
+                "Already a variable with this name in this scope.",
+            );
+            return;
+        }
+
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn resolve_local(&mut self, expr: &Expr<'a>, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.interpreter.resolve(expr, depth);
+                return;
+            }
+        }
+
+        // Not found in any local scope, so assume the variable is global.
+    }
+}
+
+impl<'a> ExprVisitor<'a, ()> for Resolver<'a, '_> {
+    fn visit_expr(&mut self, expr: &Expr<'a>) {
+        match expr {
+            Expr::Assign { name, value } => {
+                self.resolve_expr(value);
+                self.resolve_local(expr, name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expr(callee);
+
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expr(object),
+            Expr::Grouping { expression, .. } => self.resolve_expr(expression),
+            Expr::Literal { .. } => {}
+            Expr::Set { object, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::Super { keyword, .. } => {
+                if self.current_class == ClassType::None {
+                    self.error(
+                        ErrorCode::SuperOutsideClass,
+                        keyword,
+                        "Can't use 'super' outside of a class.",
+                    );
+                } else if self.current_class != ClassType::Subclass {
+                    self.error(
+                        ErrorCode::SuperWithoutSuperclass,
+                        keyword,
+                        "Can't use 'super' in a class with no superclass.",
+                    );
+                }
+
+                self.resolve_local(expr, keyword);
+            }
+            Expr::This(keyword) => {
+                if self.current_class == ClassType::None {
+                    self.error(
+                        ErrorCode::ThisOutsideClass,
+                        keyword,
+                        "Can't use 'this' outside of a class.",
+                    );
+                    return;
+                }
+
+                self.resolve_local(expr, keyword);
+            }
+            Expr::Unary { right, .. } => self.resolve_expr(right),
+            Expr::Variable(name) => {
+                if let Some(scope) = self.scopes.last() {
+                    if scope.get(&name.lexeme) == Some(&false) {
+                        self.error(
+                            ErrorCode::ReadInOwnInitializer,
+                            name,
+                            "Can't read local variable in its own initializer.",
+                        );
+                    }
+                }
+
+                self.resolve_local(expr, name);
+            }
+        }
+    }
+}
+
+impl<'a> StmtVisitor<'a, ()> for Resolver<'a, '_> {
+    fn visit_stmt(&mut self, stmt: &Stmt<'a>) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.resolve(statements);
+                self.end_scope();
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable(superclass_name) = superclass {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(
+                                ErrorCode::InheritFromSelf,
+                                superclass_name,
+                                "A class can't inherit from itself.",
+                            );
+                        }
+                    }
+
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expr(superclass);
+
+                    // Methods of a subclass close over an extra scope that
+                    // binds `super`.
+                    self.begin_scope();
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert("super".to_string(), true);
+                    }
+                }
+
+                // Methods close over a scope that holds nothing but `this`.
+                self.begin_scope();
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert("this".to_string(), true);
+                }
+
+                for method in methods {
+                    let declaration = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+
+                    self.resolve_function(method, declaration);
+                }
+
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Expression(expression) | Stmt::Print(expression) => {
+                self.resolve_expr(expression);
+            }
+            Stmt::Function(function) => {
+                // Define the name eagerly so the function can refer to itself
+                // recursively inside its own body.
+                self.declare(function.name);
+                self.define(function.name);
+
+                self.resolve_function(function, FunctionType::Function);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.error(
+                        ErrorCode::TopLevelReturn,
+                        keyword,
+                        "Can't return from top-level code.",
+                    );
+                }
+
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(
+                            ErrorCode::ReturnValueFromInitializer,
+                            keyword,
+                            "Can't return a value from an initializer.",
+                        );
+                    }
+
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::While { condition, body } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            }
+        }
+    }
+}
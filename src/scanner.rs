@@ -1,68 +1,86 @@
 use crate::literal::Literal;
+use crate::span::Span;
 use crate::token::Token;
 use crate::token_type::TokenType;
-use crate::Context;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use crate::diagnostic::Diagnostics;
+use crate::error_code::ErrorCode;
 use std::str;
 
+// Reserved words, which scan as their own token types rather than as
+// identifiers.
+pub const KEYWORDS: [(&str, TokenType); 16] = [
+    ("and", TokenType::And),
+    ("class", TokenType::Class),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+];
+
 pub struct Scanner {
     source: Vec<u8>,
+    // Where the source sits within the whole program, for sources that are
+    // fed in piece by piece like REPL input.
+    base_offset: usize,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
-    keywords: HashMap<Vec<u8>, TokenType>, // Optimize this to be static somehow
+    // Byte offset where the current line begins, for working out columns.
+    line_start: usize,
+    start_line: usize,
+    start_column: usize,
 }
 
 impl Scanner {
     pub fn new(source: Vec<u8>) -> Scanner {
-        let mut keywords = HashMap::new();
-
-        keywords.insert(b"and".to_vec(), TokenType::And);
-        keywords.insert(b"class".to_vec(), TokenType::Class);
-        keywords.insert(b"else".to_vec(), TokenType::Else);
-        keywords.insert(b"false".to_vec(), TokenType::False);
-        keywords.insert(b"for".to_vec(), TokenType::For);
-        keywords.insert(b"fun".to_vec(), TokenType::Fun);
-        keywords.insert(b"if".to_vec(), TokenType::If);
-        keywords.insert(b"nil".to_vec(), TokenType::Nil);
-        keywords.insert(b"or".to_vec(), TokenType::Or);
-        keywords.insert(b"print".to_vec(), TokenType::Print);
-        keywords.insert(b"return".to_vec(), TokenType::Return);
-        keywords.insert(b"super".to_vec(), TokenType::Super);
-        keywords.insert(b"this".to_vec(), TokenType::This);
-        keywords.insert(b"true".to_vec(), TokenType::True);
-        keywords.insert(b"var".to_vec(), TokenType::Var);
-        keywords.insert(b"while".to_vec(), TokenType::While);
+        Scanner::new_at(source, 0, 1)
+    }
 
+    // Scans source that starts at the given byte offset and line.
+    pub(crate) fn new_at(source: Vec<u8>, offset: usize, line: usize) -> Scanner {
         Scanner {
             source,
+            base_offset: offset,
             tokens: vec![],
             start: 0,
             current: 0,
-            line: 1,
-            keywords,
+            line,
+            line_start: 0,
+            start_line: line,
+            start_column: 1,
         }
     }
 
-    pub fn scan_tokens(mut self, context: &RefCell<Context>) -> Vec<Token> {
+    pub fn scan_tokens(mut self, diagnostics: &mut Diagnostics) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
-            self.scan_token(&mut context.borrow_mut());
+            self.start_line = self.line;
+            self.start_column = self.column(self.start);
+            self.scan_token(diagnostics);
         }
 
         self.tokens.push(Token {
             r#type: TokenType::Eof,
             lexeme: "".to_string(),
             literal: Literal::Nil,
-            line: self.line,
+            span: self.span_at(self.current),
         });
 
         self.tokens
     }
 
-    fn scan_token(&mut self, context: &mut Context) {
+    fn scan_token(&mut self, diagnostics: &mut Diagnostics) {
         let c = self.advance();
 
         match c {
@@ -102,15 +120,25 @@ impl Scanner {
                 }
             }
             b' ' | b'\r' | b'\t' => {}
-            b'\n' => self.line += 1,
-            b'"' => self.string(context),
+            b'\n' => self.new_line(self.current),
+            b'"' => self.string(diagnostics),
             _ => {
                 if c.is_ascii_digit() {
                     self.number();
                 } else if Scanner::is_alpha(c) {
                     self.identifier();
                 } else {
-                    context.error(self.line, "Unexpected character.")
+                    // Report a multi-byte character once rather than once
+                    // per byte.
+                    while (self.peek() & 0xC0) == 0x80 {
+                        self.advance();
+                    }
+
+                    diagnostics.error(
+                        ErrorCode::UnexpectedCharacter,
+                        self.current_span(),
+                        "Unexpected character.",
+                    );
                 }
             }
         }
@@ -122,8 +150,11 @@ impl Scanner {
         }
 
         let text = &self.source[self.start..self.current];
-        let r#type = self.keywords.get(text).unwrap_or(&TokenType::Identifier);
-        self.add_token(*r#type);
+        let r#type = KEYWORDS
+            .iter()
+            .find(|(keyword, _)| keyword.as_bytes() == text)
+            .map_or(TokenType::Identifier, |(_, r#type)| *r#type);
+        self.add_token(r#type);
     }
 
     fn number(&mut self) {
@@ -148,16 +179,20 @@ impl Scanner {
         self.add_token_with_literal(TokenType::Number, Literal::Number(number));
     }
 
-    fn string(&mut self, context: &mut Context) {
+    fn string(&mut self, diagnostics: &mut Diagnostics) {
         while self.peek() != b'"' && !self.is_at_end() {
             if self.peek() == b'\n' {
-                self.line += 1;
+                self.new_line(self.current + 1);
             }
             self.advance();
         }
 
         if self.is_at_end() {
-            context.error(self.line, "Unterminated string.");
+            diagnostics.error(
+                ErrorCode::UnterminatedString,
+                self.span_at(self.current),
+                "Unterminated string.",
+            );
             return;
         }
 
@@ -201,6 +236,36 @@ impl Scanner {
         }
     }
 
+    fn new_line(&mut self, line_start: usize) {
+        self.line += 1;
+        self.line_start = line_start;
+    }
+
+    // Columns count characters rather than bytes, so skip UTF-8
+    // continuation bytes between the start of the line and `offset`.
+    fn column(&self, offset: usize) -> usize {
+        self.source[self.line_start..offset]
+            .iter()
+            .filter(|byte| (*byte & 0xC0) != 0x80)
+            .count()
+            + 1
+    }
+
+    // The span of the lexeme scanned so far.
+    fn current_span(&self) -> Span {
+        Span::new(
+            self.base_offset + self.start,
+            self.current - self.start,
+            self.start_line,
+            self.start_column,
+        )
+    }
+
+    // An empty span at `offset` on the current line.
+    fn span_at(&self, offset: usize) -> Span {
+        Span::new(self.base_offset + offset, 0, self.line, self.column(offset))
+    }
+
     fn is_alpha(c: u8) -> bool {
         c.is_ascii_alphabetic() || c == b'_'
     }
@@ -243,7 +308,7 @@ impl Scanner {
             r#type,
             lexeme: str::from_utf8(text).unwrap().to_string(),
             literal,
-            line: self.line,
+            span: self.current_span(),
         });
     }
 }
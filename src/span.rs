@@ -0,0 +1,40 @@
+// A region of the source code. `line` and `column` are where the region
+// starts and are both 1-based, with columns counted in characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(offset: usize, length: usize, line: usize, column: usize) -> Span {
+        Span {
+            offset,
+            length,
+            line,
+            column,
+        }
+    }
+
+    // The smallest span covering both this span and `other`.
+    pub fn to(self, other: Span) -> Span {
+        let (start, end) = if self.offset <= other.offset {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        let end_offset = (end.offset + end.length).max(start.offset + start.length);
+
+        Span {
+            length: end_offset - start.offset,
+            ..start
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        self.offset + self.length
+    }
+}
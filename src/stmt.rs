@@ -1,17 +1,46 @@
+use std::rc::Rc;
+
 use crate::{expr::Expr, token::Token};
 
-pub trait Visitor<R> {
-    fn visit_stmt(&mut self, stmt: &Stmt) -> R;
+pub trait Visitor<'a, R> {
+    fn visit_stmt(&mut self, stmt: &Stmt<'a>) -> R;
+}
+
+pub struct Function<'a> {
+    pub name: &'a Token,
+    pub params: Vec<&'a Token>,
+    pub body: Vec<Stmt<'a>>,
 }
 
 pub enum Stmt<'a> {
     Block {
         statements: Vec<Stmt<'a>>,
     },
+    Class {
+        name: &'a Token,
+        superclass: Option<&'a Expr<'a>>,
+        methods: Vec<Rc<Function<'a>>>,
+    },
     Expression(&'a Expr<'a>),
+    // Function declarations are shared with the function values created from
+    // them, which outlive the statement list they were parsed into.
+    Function(Rc<Function<'a>>),
+    If {
+        condition: &'a Expr<'a>,
+        then_branch: Box<Stmt<'a>>,
+        else_branch: Option<Box<Stmt<'a>>>,
+    },
     Print(&'a Expr<'a>),
+    Return {
+        keyword: &'a Token,
+        value: Option<&'a Expr<'a>>,
+    },
     Var {
         name: &'a Token,
         initializer: Option<&'a Expr<'a>>,
     },
+    While {
+        condition: &'a Expr<'a>,
+        body: Box<Stmt<'a>>,
+    },
 }
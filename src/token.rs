@@ -1,12 +1,12 @@
 use std::fmt::Display;
 
-use crate::{token_type::TokenType, literal::Literal};
+use crate::{token_type::TokenType, literal::Literal, span::Span};
 
 pub struct Token {
     pub r#type: TokenType,
     pub lexeme: String,
     pub literal: Literal,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl Display for Token {
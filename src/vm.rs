@@ -0,0 +1,827 @@
+pub mod bytecode;
+pub mod chunk;
+pub mod compiler;
+pub mod debug;
+pub mod object;
+pub mod value;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error_code::ErrorCode,
+    heap::{GcConfig, GcStats, Heap, Trace},
+    interpreter::{RuntimeError, StackFrame},
+    output::{self, Output},
+    span::Span,
+};
+
+use self::{
+    chunk::{Constant, Function, OpCode},
+    object::{BoundMethod, Class, Closure, Instance, Native, Upvalue},
+    value::Value,
+};
+
+// How many calls can be in progress at once before the VM reports a stack
+// overflow.
+const FRAMES_MAX: usize = 1024;
+
+// Output that helps to debug the compiler and the VM. It is written to the
+// log set with `set_log`, which throws it away unless the host supplies one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugConfig {
+    // Write the bytecode of every script to the log before running it.
+    pub disassemble: bool,
+    // Write the stack and the instruction about to run to the log at every
+    // step.
+    pub trace_exec: bool,
+}
+
+// A function call in progress. `slots` is where the function's stack window
+// starts: the function itself, or `this` for methods, followed by its
+// arguments and locals.
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    slots: usize,
+}
+
+// A stack-based virtual machine that runs functions compiled by
+// `vm::compiler::Compiler`, as a faster alternative to the tree-walk
+// `Interpreter`. Globals persist between calls to `interpret`, the way the
+// REPL needs.
+pub struct Vm {
+    frames: Vec<CallFrame>,
+    stack: Vec<Value>,
+    globals: HashMap<Rc<str>, Value>,
+    // Upvalues still pointing into the stack, sorted by slot, so that
+    // closures capturing the same variable share one upvalue.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    init_string: Rc<str>,
+    heap: Heap<'static>,
+    debug: DebugConfig,
+    // Where `print` statements write.
+    output: Output,
+    log: Output,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        Vm::with_gc(GcConfig::default())
+    }
+
+    pub fn with_gc(config: GcConfig) -> Vm {
+        let mut vm = Vm {
+            frames: vec![],
+            stack: vec![],
+            globals: HashMap::new(),
+            open_upvalues: vec![],
+            init_string: "init".into(),
+            heap: Heap::new(config),
+            debug: DebugConfig::default(),
+            output: output::stdout(),
+            log: output::discard(),
+        };
+
+        vm.define_native("clock", 0, |_| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| RuntimeError::from_native("System clock is before the Unix epoch."))?;
+
+            Ok(Value::Number(now.as_secs_f64()))
+        });
+
+        vm
+    }
+
+    // Exposes a function implemented in Rust to Lox code as a global.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        let function = Box::new(function);
+        let native = Value::Native(Rc::new(Native { arity, function }));
+        self.globals.insert(name.into(), native);
+    }
+
+    pub fn set_debug(&mut self, config: DebugConfig) {
+        self.debug = config;
+    }
+
+    pub fn debug_config(&self) -> DebugConfig {
+        self.debug
+    }
+
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    pub fn output(&self) -> Output {
+        Rc::clone(&self.output)
+    }
+
+    // Sets where the debugging output turned on by `set_debug` is written,
+    // along with the collector's log.
+    pub fn set_log(&mut self, log: Output) {
+        self.heap.set_log(Rc::clone(&log));
+        self.log = log;
+    }
+
+    pub fn log(&self) -> Output {
+        Rc::clone(&self.log)
+    }
+
+    // Runs a compiled script, returning the value it returns: nil for a
+    // whole script, or the value of the expression for the REPL.
+    pub(crate) fn interpret(&mut self, script: Rc<Function>) -> Result<Value, RuntimeError> {
+        let closure = Rc::new(Closure {
+            function: script,
+            upvalues: vec![],
+        });
+        self.track(&closure);
+
+        self.stack.push(Value::Closure(closure.clone()));
+        let result = self.call(closure, 0).and_then(|()| self.run());
+
+        result.map_err(|error| {
+            let error = self.capture_trace(error);
+
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+
+            error
+        })
+    }
+
+    // The global variables defined so far, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals: Vec<_> = self
+            .globals
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+
+        globals.sort_by(|(left, _), (right, _)| left.cmp(right));
+        globals
+    }
+
+    // Registers a newly allocated object with the garbage collector, which
+    // may run a collection right away.
+    pub(crate) fn track<T: Trace<'static> + 'static>(&mut self, object: &Rc<T>) {
+        if self.heap.track(object) {
+            self.collect_garbage();
+        }
+    }
+
+    pub fn collect_garbage(&mut self) {
+        let stack = &self.stack;
+        let globals = &self.globals;
+        let frames = &self.frames;
+        let open_upvalues = &self.open_upvalues;
+
+        self.heap.collect(|tracer| {
+            for value in stack.iter().chain(globals.values()) {
+                value.trace(tracer);
+            }
+
+            for frame in frames {
+                tracer.object(&frame.closure);
+            }
+
+            for upvalue in open_upvalues {
+                tracer.object(upvalue);
+            }
+        });
+    }
+
+    pub fn gc_config(&self) -> GcConfig {
+        self.heap.config()
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    // Prints the stack and the instruction about to run, the way clox does
+    // with DEBUG_TRACE_EXECUTION.
+    fn trace_instruction(&self) {
+        let mut stack = " ".repeat(10);
+        for value in &self.stack {
+            write!(stack, "[ {value} ]").unwrap();
+        }
+
+        let frame = self.frame();
+        let (instruction, _) =
+            debug::disassemble_instruction(&frame.closure.function.chunk, frame.ip);
+        let _ = writeln!(self.log.borrow_mut(), "{stack}\n{instruction}");
+    }
+
+    fn run(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            if self.debug.trace_exec {
+                self.trace_instruction();
+            }
+
+            let byte = self.read_byte();
+            let Some(op) = OpCode::from_byte(byte) else {
+                return Err(self.error(
+                    ErrorCode::UnknownOperator,
+                    format!("Unknown opcode {byte}."),
+                ));
+            };
+
+            match op {
+                OpCode::Constant => {
+                    let value = match self.read_constant() {
+                        Constant::Number(number) => Value::Number(number),
+                        Constant::String(string) => Value::String(string),
+                        Constant::Function(_) => {
+                            unreachable!("functions are loaded by OpCode::Closure")
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(global) => *global = value,
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let Value::Instance(instance) = self.peek(0).clone() else {
+                        return Err(self.property_on_non_instance(0));
+                    };
+
+                    let field = instance.borrow().fields.get(&name).cloned();
+                    match field {
+                        Some(value) => {
+                            self.pop();
+                            self.stack.push(value);
+                        }
+                        None => {
+                            let class = instance.borrow().class.clone();
+                            self.bind_method(&class, &name)?;
+                        }
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let Value::Instance(instance) = self.peek(1).clone() else {
+                        return Err(self.field_on_non_instance());
+                    };
+
+                    let value = self.pop();
+                    instance.borrow_mut().fields.insert(name, value.clone());
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.invalid_bytecode("`super` is not a class."));
+                    };
+                    self.bind_method(&superclass, &name)?;
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(left == right));
+                }
+                OpCode::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(left != right));
+                }
+                OpCode::Greater => self.comparison(|left, right| left > right)?,
+                OpCode::GreaterEqual => self.comparison(|left, right| left >= right)?,
+                OpCode::Less => self.comparison(|left, right| left < right)?,
+                OpCode::LessEqual => self.comparison(|left, right| left <= right)?,
+                OpCode::Add => {
+                    let result = match (self.peek(1), self.peek(0)) {
+                        (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+                        (Value::String(left), Value::String(right)) => {
+                            Value::String(format!("{left}{right}").into())
+                        }
+                        (left, right) => {
+                            let note = describe_operands(left, right);
+                            return Err(self
+                                .error(
+                                    ErrorCode::OperandsNotAddable,
+                                    "Operands must be two numbers or two strings.",
+                                )
+                                .with_note(note));
+                        }
+                    };
+
+                    self.pop();
+                    self.pop();
+                    self.stack.push(result);
+                }
+                OpCode::Subtract => self.arithmetic(|left, right| left - right)?,
+                OpCode::Multiply => self.arithmetic(|left, right| left * right)?,
+                OpCode::Divide => self.arithmetic(|left, right| left / right)?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(value.is_falsey()));
+                }
+                OpCode::Negate => {
+                    let Value::Number(number) = self.peek(0) else {
+                        let note = format!("the operand is {}", self.peek(0).type_name());
+                        return Err(self
+                            .error(ErrorCode::OperandNotNumber, "Operand must be a number.")
+                            .with_note(note));
+                    };
+
+                    let negated = Value::Number(-number);
+                    self.pop();
+                    self.stack.push(negated);
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    let _ = writeln!(self.output.borrow_mut(), "{value}");
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16();
+                    self.frame_mut().ip += offset as usize;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16();
+                    if self.peek(0).is_falsey() {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_u16();
+                    self.frame_mut().ip -= offset as usize;
+                }
+                OpCode::Call => {
+                    let count = self.read_byte() as usize;
+                    self.call_value(self.peek(count).clone(), count)?;
+                }
+                OpCode::Invoke => {
+                    let name = self.read_string();
+                    let count = self.read_byte() as usize;
+                    self.invoke(&name, count)?;
+                }
+                OpCode::SuperInvoke => {
+                    let name = self.read_string();
+                    let count = self.read_byte() as usize;
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.invalid_bytecode("`super` is not a class."));
+                    };
+                    self.invoke_from_class(&superclass, &name, count)?;
+                }
+                OpCode::Closure => {
+                    let Constant::Function(function) = self.read_constant() else {
+                        unreachable!("closures are always created from functions");
+                    };
+
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+
+                        let upvalue = if is_local {
+                            self.capture_upvalue(self.frame().slots + index)
+                        } else {
+                            self.frame().closure.upvalues[index].clone()
+                        };
+                        upvalues.push(upvalue);
+                    }
+
+                    let closure = Rc::new(Closure { function, upvalues });
+                    self.track(&closure);
+                    self.stack.push(Value::Closure(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+
+                    self.stack.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = Rc::new(RefCell::new(Class::new(name)));
+                    self.track(&class);
+                    self.stack.push(Value::Class(class));
+                }
+                OpCode::Inherit => {
+                    let name = self.read_string();
+                    let Value::Class(superclass) = self.peek(1).clone() else {
+                        let note = format!("'{name}' is {}", self.peek(1).type_name());
+                        return Err(self
+                            .error(ErrorCode::SuperclassNotClass, "Superclass must be a class.")
+                            .with_note(note));
+                    };
+
+                    // Methods are copied down before the subclass defines its
+                    // own, which then override them.
+                    let Value::Class(subclass) = self.pop() else {
+                        return Err(self.invalid_bytecode("Only classes can inherit."));
+                    };
+                    let methods = superclass.borrow().methods.clone();
+                    subclass.borrow_mut().methods.extend(methods);
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let Value::Closure(method) = self.pop() else {
+                        return Err(self.invalid_bytecode("Methods must be closures."));
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        return Err(self.invalid_bytecode("Methods must be defined on a class."));
+                    };
+                    class.borrow_mut().methods.insert(name, method);
+                }
+            }
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.closure.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Constant {
+        let index = self.read_u16() as usize;
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> Rc<str> {
+        match self.read_constant() {
+            Constant::String(string) => string,
+            _ => unreachable!("names are always string constants"),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the stack should not be empty")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn arithmetic(&mut self, operation: fn(f64, f64) -> f64) -> Result<(), RuntimeError> {
+        let (left, right) = self.number_operands()?;
+        self.stack.push(Value::Number(operation(left, right)));
+        Ok(())
+    }
+
+    fn comparison(&mut self, operation: fn(f64, f64) -> bool) -> Result<(), RuntimeError> {
+        let (left, right) = self.number_operands()?;
+        self.stack.push(Value::Bool(operation(left, right)));
+        Ok(())
+    }
+
+    fn number_operands(&mut self) -> Result<(f64, f64), RuntimeError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(left), Value::Number(right)) => {
+                let operands = (*left, *right);
+                self.pop();
+                self.pop();
+                Ok(operands)
+            }
+            (left, right) => {
+                let note = describe_operands(left, right);
+                Err(self
+                    .error(ErrorCode::OperandsNotNumbers, "Operands must be numbers.")
+                    .with_note(note))
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, count: usize) -> Result<(), RuntimeError> {
+        match callee {
+            Value::Closure(closure) => self.call(closure, count),
+            Value::BoundMethod(bound) => {
+                let slot = self.stack.len() - count - 1;
+                self.stack[slot] = bound.receiver.clone();
+                self.call(bound.method.clone(), count)
+            }
+            Value::Class(class) => {
+                let instance = Rc::new(RefCell::new(Instance::new(class.clone())));
+                self.track(&instance);
+
+                let slot = self.stack.len() - count - 1;
+                self.stack[slot] = Value::Instance(instance);
+
+                let initializer = class.borrow().methods.get(&self.init_string).cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, count),
+                    None if count != 0 => Err(self.wrong_arity(0, count)),
+                    None => Ok(()),
+                }
+            }
+            Value::Native(native) => {
+                if count != native.arity {
+                    return Err(self.wrong_arity(native.arity, count));
+                }
+
+                let arguments = self.stack.len() - count;
+                let result =
+                    (native.function)(&self.stack[arguments..]).map_err(|error| RuntimeError {
+                        span: self.current_span(),
+                        ..error
+                    })?;
+
+                self.stack.truncate(arguments - 1);
+                self.stack.push(result);
+                Ok(())
+            }
+            callee => {
+                let note = format!("the called value is {}", callee.type_name());
+                Err(self
+                    .error(
+                        ErrorCode::NotCallable,
+                        "Can only call functions and classes.",
+                    )
+                    .with_note(note))
+            }
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, count: usize) -> Result<(), RuntimeError> {
+        let arity = closure.function.arity as usize;
+        if count != arity {
+            return Err(self.wrong_arity(arity, count));
+        }
+
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error(ErrorCode::StackOverflow, "Stack overflow."));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - count - 1,
+        });
+
+        Ok(())
+    }
+
+    // Calls a method on the receiver below the arguments, without creating
+    // a bound method first. A field holding a function is called instead if
+    // there is one.
+    fn invoke(&mut self, name: &Rc<str>, count: usize) -> Result<(), RuntimeError> {
+        let Value::Instance(instance) = self.peek(count).clone() else {
+            return Err(self.property_on_non_instance(count));
+        };
+
+        let field = instance.borrow().fields.get(name).cloned();
+        if let Some(field) = field {
+            let slot = self.stack.len() - count - 1;
+            self.stack[slot] = field.clone();
+            return self.call_value(field, count);
+        }
+
+        let class = instance.borrow().class.clone();
+        self.invoke_from_class(&class, name, count)
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: &Rc<RefCell<Class>>,
+        name: &Rc<str>,
+        count: usize,
+    ) -> Result<(), RuntimeError> {
+        let method = class.borrow().methods.get(name).cloned();
+        match method {
+            Some(method) => self.call(method, count),
+            None => Err(self.undefined_property(name)),
+        }
+    }
+
+    // Replaces the instance on top of the stack with one of its class's
+    // methods bound to it.
+    fn bind_method(
+        &mut self,
+        class: &Rc<RefCell<Class>>,
+        name: &Rc<str>,
+    ) -> Result<(), RuntimeError> {
+        let method = class.borrow().methods.get(name).cloned();
+        let Some(method) = method else {
+            return Err(self.undefined_property(name));
+        };
+
+        let receiver = self.pop();
+        let bound = Rc::new(BoundMethod { receiver, method });
+        self.track(&bound);
+        self.stack.push(Value::BoundMethod(bound));
+
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let position = self.open_upvalues.partition_point(
+            |upvalue| matches!(&*upvalue.borrow(), Upvalue::Open(open) if *open < slot),
+        );
+
+        if let Some(existing) = self.open_upvalues.get(position) {
+            if matches!(&*existing.borrow(), Upvalue::Open(open) if *open == slot) {
+                return existing.clone();
+            }
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position, upvalue.clone());
+        self.track(&upvalue);
+        upvalue
+    }
+
+    // Moves every captured variable at or above `last` off the stack and
+    // into its upvalue, as the slots are about to be discarded.
+    fn close_upvalues(&mut self, last: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let Upvalue::Open(slot) = *upvalue.borrow() else {
+                unreachable!("only open upvalues are tracked");
+            };
+            if slot < last {
+                break;
+            }
+
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+            self.open_upvalues.pop();
+        }
+    }
+
+    // The span of the instruction being executed. Every operand is written
+    // with the span of its instruction, apart from the argument count of an
+    // invoke, which points at the call's parenthesis instead, so errors and
+    // stack traces for calls point there too.
+    fn current_span(&self) -> Span {
+        let frame = self.frame();
+        frame.closure.function.chunk.span_at(frame.ip - 1)
+    }
+
+    // The span of an instruction's name operand, for errors that point at a
+    // property rather than at the call it belongs to.
+    fn name_span(&self, distance: usize) -> Span {
+        let frame = self.frame();
+        frame.closure.function.chunk.span_at(frame.ip - distance)
+    }
+
+    fn error<T: Into<String>>(&self, code: ErrorCode, message: T) -> RuntimeError {
+        RuntimeError::at(code, self.current_span(), message)
+    }
+
+    fn undefined_variable(&self, name: &str) -> RuntimeError {
+        self.error(
+            ErrorCode::UndefinedVariable,
+            format!("Undefined variable '{name}'."),
+        )
+    }
+
+    // Names are two-byte operands, and invokes follow them with a one-byte
+    // argument count, so two bytes back is always part of the name.
+    fn undefined_property(&self, name: &str) -> RuntimeError {
+        RuntimeError::at(
+            ErrorCode::UndefinedProperty,
+            self.name_span(2),
+            format!("Undefined property '{name}'."),
+        )
+    }
+
+    fn property_on_non_instance(&self, distance: usize) -> RuntimeError {
+        RuntimeError::at(
+            ErrorCode::PropertyOnNonInstance,
+            self.name_span(2),
+            "Only instances have properties.",
+        )
+        .with_note(format!("the value is {}", self.peek(distance).type_name()))
+    }
+
+    fn field_on_non_instance(&self) -> RuntimeError {
+        self.error(ErrorCode::FieldOnNonInstance, "Only instances have fields.")
+            .with_note(format!("the value is {}", self.peek(1).type_name()))
+    }
+
+    // The verifier checks what a `.loxc` file refers to, but not the kinds of
+    // values its instructions will find on the stack, so the few instructions
+    // that only the compiler's own code makes safe check for themselves.
+    fn invalid_bytecode(&self, message: &str) -> RuntimeError {
+        self.error(ErrorCode::InvalidBytecode, format!("Invalid bytecode: {message}"))
+    }
+
+    fn wrong_arity(&self, arity: usize, count: usize) -> RuntimeError {
+        self.error(
+            ErrorCode::WrongArity,
+            format!("Expected {arity} arguments but got {count}."),
+        )
+    }
+
+    // Records the functions that were running when the error happened,
+    // innermost first, with the line each one was executing.
+    fn capture_trace(&self, mut error: RuntimeError) -> RuntimeError {
+        error.trace = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| StackFrame {
+                function: frame
+                    .closure
+                    .function
+                    .name
+                    .as_ref()
+                    .map(|name| name.to_string()),
+                line: frame.closure.function.chunk.span_at(frame.ip - 1).line,
+            })
+            .collect();
+
+        error
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+fn describe_operands(left: &Value, right: &Value) -> String {
+    format!(
+        "the left operand is {} and the right operand is {}",
+        left.type_name(),
+        right.type_name()
+    )
+}
@@ -0,0 +1,539 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::span::Span;
+
+use super::chunk::{Chunk, Constant, Function, OpCode};
+
+// The `.loxc` format that compiled scripts are cached in. All integers are
+// little-endian:
+//
+//     magic     4 bytes   "LOXC"
+//     version   u16       FORMAT_VERSION
+//     checksum  u32       CRC-32 of everything after the header
+//     source    string    the script's source, for quoting in diagnostics
+//     script    function  the top-level script
+//
+// A function is its name (a u8 flag followed by a string when present),
+// arity (u8), upvalue count (u16), code (u32 length and bytes), constants
+// (u32 count, each a tag byte followed by an f64, a string or a nested
+// function) and line table (u32 count of start offsets and spans, each
+// field a u32). Strings are a u32 length followed by UTF-8.
+pub const MAGIC: &[u8; 4] = b"LOXC";
+
+// Bump this whenever the instruction set or the layout above changes, so
+// that files written by an older build are rejected instead of misread.
+pub const FORMAT_VERSION: u16 = 1;
+
+const HEADER_LENGTH: usize = 10;
+
+const NUMBER_TAG: u8 = 0;
+const STRING_TAG: u8 = 1;
+const FUNCTION_TAG: u8 = 2;
+
+// Why a `.loxc` file couldn't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    NotBytecode,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Corrupt(&'static str),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::NotBytecode => write!(f, "not a compiled Lox file"),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "compiled with bytecode format version {version}, but this build runs version \
+                 {FORMAT_VERSION}; compile the script again"
+            ),
+            LoadError::ChecksumMismatch => write!(f, "the file is corrupt (checksum mismatch)"),
+            LoadError::Corrupt(reason) => write!(f, "the file is corrupt ({reason})"),
+        }
+    }
+}
+
+// Serializes a compiled script along with its source. Spans are stored
+// relative to `start`, the offset and line the source begins at in the
+// session it was compiled in, so the file can be run in any session.
+pub fn encode(source: &str, script: &Function, start: (usize, usize)) -> Vec<u8> {
+    let mut payload = vec![];
+    write_string(&mut payload, source);
+    write_function(&mut payload, script, start);
+
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+// Checks and deserializes a file written by `encode`, returning the source
+// and the script. Spans are moved to begin at `start`, where the source is
+// going to be added in the session that runs it.
+pub fn decode(bytes: &[u8], start: (usize, usize)) -> Result<(String, Rc<Function>), LoadError> {
+    if bytes.len() < HEADER_LENGTH || &bytes[..4] != MAGIC {
+        return Err(LoadError::NotBytecode);
+    }
+
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let checksum = u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+    let payload = &bytes[HEADER_LENGTH..];
+    if crc32(payload) != checksum {
+        return Err(LoadError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader {
+        bytes: payload,
+        position: 0,
+    };
+
+    let source = reader.string()?.to_string();
+    let script = reader.function(start)?;
+
+    // The VM runs the script without any enclosing function to capture
+    // variables from.
+    if script.upvalue_count != 0 {
+        return Err(LoadError::Corrupt("the script captures variables"));
+    }
+
+    if reader.position != payload.len() {
+        return Err(LoadError::Corrupt("unexpected data after the script"));
+    }
+
+    Ok((source, Rc::new(script)))
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) {
+    let value = u32::try_from(value).expect("bytecode too large to serialize");
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+    write_u32(bytes, string.len());
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+fn write_function(bytes: &mut Vec<u8>, function: &Function, start: (usize, usize)) {
+    match &function.name {
+        Some(name) => {
+            bytes.push(1);
+            write_string(bytes, name);
+        }
+        None => bytes.push(0),
+    }
+
+    bytes.push(function.arity);
+    // There can be at most 256 upvalues, one more than fits in a byte.
+    bytes.extend_from_slice(&(function.upvalue_count as u16).to_le_bytes());
+
+    let chunk = &function.chunk;
+    write_u32(bytes, chunk.code.len());
+    bytes.extend_from_slice(&chunk.code);
+
+    write_u32(bytes, chunk.constants.len());
+    for constant in &chunk.constants {
+        match constant {
+            Constant::Number(number) => {
+                bytes.push(NUMBER_TAG);
+                bytes.extend_from_slice(&number.to_le_bytes());
+            }
+            Constant::String(string) => {
+                bytes.push(STRING_TAG);
+                write_string(bytes, string);
+            }
+            Constant::Function(function) => {
+                bytes.push(FUNCTION_TAG);
+                write_function(bytes, function, start);
+            }
+        }
+    }
+
+    write_u32(bytes, chunk.spans().len());
+    for (offset, span) in chunk.spans() {
+        write_u32(bytes, *offset);
+        // Code with no token of its own, like the return at the end of an
+        // empty script, has a default span from before the start.
+        write_u32(bytes, span.offset.saturating_sub(start.0));
+        write_u32(bytes, span.length);
+        write_u32(bytes, span.line.saturating_sub(start.1 - 1));
+        write_u32(bytes, span.column);
+    }
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, count: usize) -> Result<&'b [u8], LoadError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(LoadError::Corrupt("unexpected end of file"))?;
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, LoadError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<usize, LoadError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn f64(&mut self) -> Result<f64, LoadError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<Rc<str>, LoadError> {
+        let length = self.u32()?;
+        let bytes = self.take(length)?;
+
+        std::str::from_utf8(bytes)
+            .map(Rc::from)
+            .map_err(|_| LoadError::Corrupt("invalid UTF-8 in a string"))
+    }
+
+    fn function(&mut self, start: (usize, usize)) -> Result<Function, LoadError> {
+        let name = match self.u8()? {
+            0 => None,
+            1 => Some(self.string()?),
+            _ => return Err(LoadError::Corrupt("invalid function name")),
+        };
+
+        let mut function = Function::new(name);
+        function.arity = self.u8()?;
+        function.upvalue_count = self.u16()? as usize;
+
+        let length = self.u32()?;
+        let code = self.take(length)?;
+
+        let count = self.u32()?;
+        let mut constants = vec![];
+        for _ in 0..count {
+            let constant = match self.u8()? {
+                NUMBER_TAG => Constant::Number(self.f64()?),
+                STRING_TAG => Constant::String(self.string()?),
+                FUNCTION_TAG => Constant::Function(Rc::new(self.function(start)?)),
+                _ => return Err(LoadError::Corrupt("unknown constant type")),
+            };
+            constants.push(constant);
+        }
+
+        let count = self.u32()?;
+        let mut spans = vec![];
+        for _ in 0..count {
+            let offset = self.u32()?;
+            let span = Span::new(
+                self.u32()? + start.0,
+                self.u32()?,
+                self.u32()? + start.1 - 1,
+                self.u32()?,
+            );
+            spans.push((offset, span));
+        }
+
+        function.chunk = chunk(code, spans)?;
+        function.chunk.constants = constants;
+        verify(&function)?;
+
+        Ok(function)
+    }
+}
+
+// Rebuilds a chunk from its code and line table, which has to start at the
+// first byte and cover the code in order.
+fn chunk(code: &[u8], spans: Vec<(usize, Span)>) -> Result<Chunk, LoadError> {
+    let valid = if code.is_empty() {
+        spans.is_empty()
+    } else {
+        spans.first().map(|(offset, _)| *offset) == Some(0)
+            && spans.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            && spans.last().is_some_and(|(offset, _)| *offset < code.len())
+    };
+
+    if !valid {
+        return Err(LoadError::Corrupt("invalid line table"));
+    }
+
+    let mut chunk = Chunk::new();
+    for (index, (offset, span)) in spans.iter().enumerate() {
+        let end = spans.get(index + 1).map_or(code.len(), |(next, _)| *next);
+
+        for byte in &code[*offset..end] {
+            chunk.write(*byte, *span);
+        }
+    }
+
+    Ok(chunk)
+}
+
+// The checksum only catches accidental damage, and the VM trusts the code it
+// runs, so the code is checked the way the compiler would have written it:
+// every instruction is known and complete, refers to constants, locals and
+// upvalues that exist, and jumps to the start of another instruction with
+// the same stack depth on every path there.
+fn verify(function: &Function) -> Result<(), LoadError> {
+    let chunk = &function.chunk;
+    let code = &chunk.code;
+
+    let mut starts = vec![false; code.len()];
+    let mut offset = 0;
+    while offset < code.len() {
+        starts[offset] = true;
+
+        let op = OpCode::from_byte(code[offset]).ok_or(LoadError::Corrupt("unknown opcode"))?;
+        let length = instruction_length(chunk, op, offset)?;
+        if offset + length > code.len() {
+            return Err(LoadError::Corrupt(
+                "instruction runs past the end of the code",
+            ));
+        }
+
+        offset += length;
+    }
+
+    // Slot zero holds the function or `this`, followed by the arguments.
+    let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = vec![(0, function.arity as usize + 1)];
+
+    while let Some((offset, depth)) = pending.pop() {
+        if offset >= code.len() {
+            return Err(LoadError::Corrupt("code runs past the end of the function"));
+        }
+
+        if !starts[offset] {
+            return Err(LoadError::Corrupt("jump into the middle of an instruction"));
+        }
+
+        match depths[offset] {
+            Some(known) if known == depth => continue,
+            Some(_) => return Err(LoadError::Corrupt("inconsistent stack depth")),
+            None => depths[offset] = Some(depth),
+        }
+
+        let op = OpCode::from_byte(code[offset]).unwrap();
+        let length = instruction_length(chunk, op, offset)?;
+        let byte = |index: usize| code[offset + index] as usize;
+
+        // How many values the instruction pops, and how many it pushes.
+        let (pops, pushes) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetGlobal
+            | OpCode::Class => (0, 1),
+            OpCode::GetLocal => {
+                check_slot(byte(1), depth)?;
+                (0, 1)
+            }
+            OpCode::SetLocal => {
+                check_slot(byte(1), depth)?;
+                (1, 1)
+            }
+            OpCode::GetUpvalue => {
+                check_upvalue(byte(1), function.upvalue_count)?;
+                (0, 1)
+            }
+            OpCode::SetUpvalue => {
+                check_upvalue(byte(1), function.upvalue_count)?;
+                (1, 1)
+            }
+            OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::JumpIfFalse => (1, 1),
+            OpCode::Pop | OpCode::DefineGlobal | OpCode::Print | OpCode::CloseUpvalue => (1, 0),
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Inherit
+            | OpCode::Method => (2, 1),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+            OpCode::Call => (byte(1) + 1, 1),
+            OpCode::Invoke => (byte(3) + 1, 1),
+            OpCode::SuperInvoke => (byte(3) + 2, 1),
+            OpCode::Closure => {
+                for pair in (offset + 3..offset + length).step_by(2) {
+                    let index = code[pair + 1] as usize;
+                    match code[pair] {
+                        // A local function captures itself, in the slot
+                        // the closure is about to be pushed into.
+                        1 => check_slot(index, depth + 1)?,
+                        0 => check_upvalue(index, function.upvalue_count)?,
+                        _ => return Err(LoadError::Corrupt("invalid captured variable")),
+                    }
+                }
+                (0, 1)
+            }
+            OpCode::Return => (1, 0),
+        };
+
+        if depth < pops {
+            return Err(LoadError::Corrupt("instruction pops an empty stack"));
+        }
+        let depth = depth - pops + pushes;
+
+        let next = offset + length;
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump => pending.push((next + chunk.read_u16(offset + 1) as usize, depth)),
+            OpCode::JumpIfFalse => {
+                pending.push((next + chunk.read_u16(offset + 1) as usize, depth));
+                pending.push((next, depth));
+            }
+            OpCode::Loop => {
+                let target = next
+                    .checked_sub(chunk.read_u16(offset + 1) as usize)
+                    .ok_or(LoadError::Corrupt("jump before the start of the code"))?;
+                pending.push((target, depth));
+            }
+            _ => pending.push((next, depth)),
+        }
+    }
+
+    Ok(())
+}
+
+// The length of the instruction at `offset`, including its operands, after
+// checking that any constant it refers to exists and has the right type.
+fn instruction_length(chunk: &Chunk, op: OpCode, offset: usize) -> Result<usize, LoadError> {
+    let constant = || -> Result<&Constant, LoadError> {
+        if offset + 3 > chunk.code.len() {
+            return Err(LoadError::Corrupt(
+                "instruction runs past the end of the code",
+            ));
+        }
+
+        chunk
+            .constants
+            .get(chunk.read_u16(offset + 1) as usize)
+            .ok_or(LoadError::Corrupt("constant out of range"))
+    };
+
+    let length = match op {
+        OpCode::Constant => match constant()? {
+            Constant::Number(_) | Constant::String(_) => 3,
+            Constant::Function(_) => return Err(LoadError::Corrupt("wrong type of constant")),
+        },
+        OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Inherit
+        | OpCode::Method
+        | OpCode::Invoke
+        | OpCode::SuperInvoke => {
+            let Constant::String(_) = constant()? else {
+                return Err(LoadError::Corrupt("wrong type of constant"));
+            };
+
+            match op {
+                OpCode::Invoke | OpCode::SuperInvoke => 4,
+                _ => 3,
+            }
+        }
+        OpCode::Closure => {
+            let Constant::Function(function) = constant()? else {
+                return Err(LoadError::Corrupt("wrong type of constant"));
+            };
+
+            3 + 2 * function.upvalue_count
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => 2,
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => 3,
+        OpCode::Nil
+        | OpCode::True
+        | OpCode::False
+        | OpCode::Pop
+        | OpCode::Equal
+        | OpCode::NotEqual
+        | OpCode::Greater
+        | OpCode::GreaterEqual
+        | OpCode::Less
+        | OpCode::LessEqual
+        | OpCode::Add
+        | OpCode::Subtract
+        | OpCode::Multiply
+        | OpCode::Divide
+        | OpCode::Not
+        | OpCode::Negate
+        | OpCode::Print
+        | OpCode::CloseUpvalue
+        | OpCode::Return => 1,
+    };
+
+    Ok(length)
+}
+
+fn check_slot(slot: usize, depth: usize) -> Result<(), LoadError> {
+    if slot < depth {
+        Ok(())
+    } else {
+        Err(LoadError::Corrupt("local slot out of range"))
+    }
+}
+
+fn check_upvalue(index: usize, count: usize) -> Result<(), LoadError> {
+    if index < count {
+        Ok(())
+    } else {
+        Err(LoadError::Corrupt("upvalue out of range"))
+    }
+}
+
+// The CRC-32 used by zip and PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
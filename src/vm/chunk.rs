@@ -0,0 +1,249 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::{interpreter::Interpreter, span::Span};
+
+// The instructions of the VM. Operands follow the opcode in the code stream:
+// constant indexes and jump offsets are two bytes, big-endian, and stack
+// slots, upvalue indexes and argument counts are one byte.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    // constant: u16
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    // slot: u8
+    GetLocal,
+    // slot: u8
+    SetLocal,
+    // name: u16
+    GetGlobal,
+    // name: u16
+    DefineGlobal,
+    // name: u16
+    SetGlobal,
+    // index: u8
+    GetUpvalue,
+    // index: u8
+    SetUpvalue,
+    // name: u16
+    GetProperty,
+    // name: u16
+    SetProperty,
+    // name: u16
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    // offset: u16
+    Jump,
+    // offset: u16
+    JumpIfFalse,
+    // offset: u16
+    Loop,
+    // arguments: u8
+    Call,
+    // name: u16, arguments: u8
+    Invoke,
+    // name: u16, arguments: u8
+    SuperInvoke,
+    // function: u16, then an (is_local: u8, index: u8) pair per upvalue
+    Closure,
+    CloseUpvalue,
+    Return,
+    // name: u16
+    Class,
+    // superclass name: u16
+    Inherit,
+    // name: u16
+    Method,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 40] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Invoke,
+        OpCode::SuperInvoke,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+}
+
+// A value known at compile time, stored in a chunk's constant pool.
+#[derive(Clone)]
+pub enum Constant {
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<Function>),
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Number(number) => write!(f, "{}", Interpreter::stringify_number(*number)),
+            Constant::String(string) => write!(f, "{string}"),
+            Constant::Function(function) => write!(f, "{function}"),
+        }
+    }
+}
+
+// A compiled function, or the top-level script when `name` is `None`.
+pub struct Function {
+    pub name: Option<Rc<str>>,
+    pub arity: u8,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl Function {
+    pub fn new(name: Option<Rc<str>>) -> Function {
+        Function {
+            name,
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::new(),
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<script>"),
+        }
+    }
+}
+
+// A sequence of bytecode along with the constants it refers to.
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    // The line table. Each entry holds the span of the source that the code
+    // from its offset up to the next entry was compiled from, so runs of
+    // bytes from the same token share one entry.
+    spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, byte: u8, span: Span) {
+        if self.spans.last().map(|(_, last)| *last) != Some(span) {
+            self.spans.push((self.code.len(), span));
+        }
+
+        self.code.push(byte);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        self.write(op as u8, span);
+    }
+
+    pub fn write_u16(&mut self, value: u16, span: Span) {
+        let [high, low] = value.to_be_bytes();
+        self.write(high, span);
+        self.write(low, span);
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    // Adds a constant to the pool, reusing an equal number or string that is
+    // already there. Returns `None` once the pool is full.
+    pub fn add_constant(&mut self, constant: Constant) -> Option<u16> {
+        let existing = self
+            .constants
+            .iter()
+            .position(|existing| match (existing, &constant) {
+                (Constant::Number(existing), Constant::Number(number)) => {
+                    existing.to_bits() == number.to_bits()
+                }
+                (Constant::String(existing), Constant::String(string)) => existing == string,
+                _ => false,
+            });
+
+        let index = existing.unwrap_or_else(|| {
+            self.constants.push(constant);
+            self.constants.len() - 1
+        });
+
+        u16::try_from(index).ok()
+    }
+
+    // The span of the source code the byte at `offset` was compiled from.
+    pub fn span_at(&self, offset: usize) -> Span {
+        let entry = self.spans.partition_point(|(start, _)| *start <= offset);
+        entry
+            .checked_sub(1)
+            .map(|entry| self.spans[entry].1)
+            .unwrap_or_default()
+    }
+
+    // The line table, as pairs of the offset where a run of bytes starts and
+    // the span they were compiled from.
+    pub fn spans(&self) -> &[(usize, Span)] {
+        &self.spans
+    }
+
+    // The span of the last byte written, for code that belongs to no token
+    // of its own, such as the cleanup at the end of a block.
+    pub fn last_span(&self) -> Option<Span> {
+        self.spans.last().map(|(_, span)| *span)
+    }
+}
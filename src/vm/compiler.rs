@@ -0,0 +1,705 @@
+use std::rc::Rc;
+
+use crate::{
+    diagnostic::Diagnostics,
+    error_code::ErrorCode,
+    expr::{Expr, Visitor as ExprVisitor},
+    literal::Literal,
+    span::Span,
+    stmt::{Function as FunctionDeclaration, Stmt, Visitor as StmtVisitor},
+    token::Token,
+    token_type::TokenType,
+};
+
+use super::chunk::{Chunk, Constant, Function, OpCode};
+
+// The VM addresses locals and upvalues with a single byte.
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Initializer,
+    Method,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    // Set when a closure captures the variable, so that it is moved off the
+    // stack when it goes out of scope instead of just being popped.
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Upvalue {
+    index: u8,
+    // Whether `index` is a local slot of the enclosing function rather than
+    // one of its upvalues.
+    is_local: bool,
+}
+
+// Where a variable lives, which decides the instructions that access it.
+enum Variable {
+    Local(u8),
+    Upvalue(u8),
+    Global(u16),
+}
+
+// The state of one function being compiled. Functions nested inside it are
+// compiled with a state of their own pushed on top.
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(name: Option<Rc<str>>, kind: FunctionKind) -> FunctionState {
+        // Slot zero holds `this` in methods, and the function being called
+        // everywhere else, where it can't be named.
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+
+        FunctionState {
+            function: Function::new(name),
+            kind,
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: vec![],
+            scope_depth: 0,
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Option<u8> {
+        self.locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+}
+
+// Compiles resolved statements into bytecode for the VM, clox-style. The
+// resolver has already reported every mistake in the program, so the only
+// errors left are limits of the bytecode format.
+pub struct Compiler<'d> {
+    diagnostics: &'d mut Diagnostics,
+    functions: Vec<FunctionState>,
+    // Set after the first error. Going over a limit usually means going
+    // over it many times in a row, so only the first one is reported.
+    had_error: bool,
+}
+
+impl<'d> Compiler<'d> {
+    pub fn new(diagnostics: &'d mut Diagnostics) -> Compiler<'d> {
+        Compiler {
+            diagnostics,
+            functions: vec![FunctionState::new(None, FunctionKind::Script)],
+            had_error: false,
+        }
+    }
+
+    // Compiles a script into the function the VM runs first. Returns `None`
+    // if the script hit one of the VM's limits.
+    pub fn compile(mut self, statements: &[Stmt]) -> Option<Rc<Function>> {
+        for statement in statements {
+            self.visit_stmt(statement);
+        }
+
+        let span = self.chunk().last_span().unwrap_or_default();
+        self.emit_return(span);
+
+        self.finish()
+    }
+
+    // Compiles a lone expression into a script that returns its value, for
+    // the REPL to show.
+    pub fn compile_expression(mut self, expression: &Expr) -> Option<Rc<Function>> {
+        self.visit_expr(expression);
+        self.emit_op(OpCode::Return, expression.span());
+
+        self.finish()
+    }
+
+    fn finish(mut self) -> Option<Rc<Function>> {
+        let script = self.functions.pop().unwrap();
+
+        if self.diagnostics.had_error() {
+            return None;
+        }
+
+        Some(Rc::new(script.function))
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().function.chunk
+    }
+
+    fn emit_op(&mut self, op: OpCode, span: Span) {
+        self.chunk().write_op(op, span);
+    }
+
+    fn emit_byte(&mut self, byte: u8, span: Span) {
+        self.chunk().write(byte, span);
+    }
+
+    fn emit_u16(&mut self, value: u16, span: Span) {
+        self.chunk().write_u16(value, span);
+    }
+
+    fn emit_with_constant(&mut self, op: OpCode, constant: u16, span: Span) {
+        self.emit_op(op, span);
+        self.emit_u16(constant, span);
+    }
+
+    // Functions without an explicit `return` hand back nil, apart from
+    // initializers, which always return the instance.
+    fn emit_return(&mut self, span: Span) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_op(OpCode::GetLocal, span);
+            self.emit_byte(0, span);
+        } else {
+            self.emit_op(OpCode::Nil, span);
+        }
+
+        self.emit_op(OpCode::Return, span);
+    }
+
+    // Emits a jump with a placeholder offset, returning where the offset is
+    // so it can be filled in once the target is known.
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit_op(op, span);
+        self.emit_u16(u16::MAX, span);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        let jump = self.chunk().code.len() - offset - 2;
+
+        let Ok(jump) = u16::try_from(jump) else {
+            self.error(ErrorCode::JumpTooLarge, span, "Too much code to jump over.");
+            return;
+        };
+
+        let [high, low] = jump.to_be_bytes();
+        self.chunk().code[offset] = high;
+        self.chunk().code[offset + 1] = low;
+    }
+
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        self.emit_op(OpCode::Loop, span);
+
+        // The offset also skips the operand of the loop instruction itself.
+        let offset = self.chunk().code.len() - loop_start + 2;
+        let Ok(offset) = u16::try_from(offset) else {
+            self.error(ErrorCode::JumpTooLarge, span, "Loop body too large.");
+            return;
+        };
+
+        self.emit_u16(offset, span);
+    }
+
+    fn make_constant(&mut self, constant: Constant, span: Span) -> u16 {
+        match self.chunk().add_constant(constant) {
+            Some(index) => index,
+            None => {
+                self.error(
+                    ErrorCode::TooManyConstants,
+                    span,
+                    "Too many constants in one chunk.",
+                );
+                0
+            }
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &str, span: Span) -> u16 {
+        self.make_constant(Constant::String(name.into()), span)
+    }
+
+    fn error(&mut self, code: ErrorCode, span: Span, message: &str) {
+        if !self.had_error {
+            self.diagnostics.error(code, span, message);
+            self.had_error = true;
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    // Discards the locals declared in the scope being closed, moving the
+    // ones that closures captured onto the heap.
+    fn end_scope(&mut self, span: Span) {
+        self.current().scope_depth -= 1;
+
+        loop {
+            let state = self.current();
+            let Some(local) = state.locals.last() else {
+                break;
+            };
+            if local.depth <= state.scope_depth {
+                break;
+            }
+
+            let op = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            state.locals.pop();
+
+            self.emit_op(op, span);
+        }
+    }
+
+    // Declares a local variable in the current scope. Its value is whatever
+    // is on top of the stack at this point.
+    fn add_local(&mut self, name: &str, token: &Token) {
+        if self.current().locals.len() == MAX_LOCALS {
+            if !self.had_error {
+                self.diagnostics.error_at_token(
+                    ErrorCode::TooManyLocals,
+                    token,
+                    "Too many local variables in function.",
+                );
+                self.had_error = true;
+            }
+            return;
+        }
+
+        let depth = self.current().scope_depth;
+        self.current().locals.push(Local {
+            name: name.to_string(),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    // Binds the value on top of the stack to `name`: as a local inside a
+    // scope, or as a global at the top level.
+    fn define_variable(&mut self, name: &Token) {
+        if self.current().scope_depth > 0 {
+            self.add_local(&name.lexeme, name);
+            return;
+        }
+
+        let constant = self.identifier_constant(&name.lexeme, name.span);
+        self.emit_with_constant(OpCode::DefineGlobal, constant, name.span);
+    }
+
+    fn resolve_variable(&mut self, name: &str, span: Span) -> Variable {
+        let function = self.functions.len() - 1;
+
+        if let Some(slot) = self.functions[function].resolve_local(name) {
+            return Variable::Local(slot);
+        }
+
+        if let Some(index) = self.resolve_upvalue(function, name, span) {
+            return Variable::Upvalue(index);
+        }
+
+        Variable::Global(self.identifier_constant(name, span))
+    }
+
+    // Finds a variable declared by a function enclosing `function`, adding
+    // an upvalue for it to every function in between.
+    fn resolve_upvalue(&mut self, function: usize, name: &str, span: Span) -> Option<u8> {
+        let enclosing = function.checked_sub(1)?;
+
+        if let Some(slot) = self.functions[enclosing].resolve_local(name) {
+            self.functions[enclosing].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, slot, true, span));
+        }
+
+        let index = self.resolve_upvalue(enclosing, name, span)?;
+        Some(self.add_upvalue(function, index, false, span))
+    }
+
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool, span: Span) -> u8 {
+        let upvalue = Upvalue { index, is_local };
+        let state = &mut self.functions[function];
+
+        if let Some(existing) = state.upvalues.iter().position(|&other| other == upvalue) {
+            return existing as u8;
+        }
+
+        if state.upvalues.len() == MAX_UPVALUES {
+            self.error(
+                ErrorCode::TooManyUpvalues,
+                span,
+                "Too many closure variables in function.",
+            );
+            return 0;
+        }
+
+        state.upvalues.push(upvalue);
+        state.function.upvalue_count = state.upvalues.len();
+        (state.upvalues.len() - 1) as u8
+    }
+
+    fn get_variable(&mut self, name: &str, span: Span) {
+        match self.resolve_variable(name, span) {
+            Variable::Local(slot) => {
+                self.emit_op(OpCode::GetLocal, span);
+                self.emit_byte(slot, span);
+            }
+            Variable::Upvalue(index) => {
+                self.emit_op(OpCode::GetUpvalue, span);
+                self.emit_byte(index, span);
+            }
+            Variable::Global(constant) => {
+                self.emit_with_constant(OpCode::GetGlobal, constant, span)
+            }
+        }
+    }
+
+    fn set_variable(&mut self, name: &str, span: Span) {
+        match self.resolve_variable(name, span) {
+            Variable::Local(slot) => {
+                self.emit_op(OpCode::SetLocal, span);
+                self.emit_byte(slot, span);
+            }
+            Variable::Upvalue(index) => {
+                self.emit_op(OpCode::SetUpvalue, span);
+                self.emit_byte(index, span);
+            }
+            Variable::Global(constant) => {
+                self.emit_with_constant(OpCode::SetGlobal, constant, span)
+            }
+        }
+    }
+
+    // Compiles a function body into a function of its own and emits the
+    // instruction that creates a closure over it.
+    fn function(&mut self, declaration: &FunctionDeclaration, kind: FunctionKind) {
+        let name = &declaration.name;
+
+        self.functions
+            .push(FunctionState::new(Some(name.lexeme.as_str().into()), kind));
+        self.begin_scope();
+
+        self.current().function.arity = declaration.params.len() as u8;
+        for param in &declaration.params {
+            self.add_local(&param.lexeme, param);
+        }
+
+        for statement in &declaration.body {
+            self.visit_stmt(statement);
+        }
+
+        let span = self.chunk().last_span().unwrap_or(name.span);
+        self.emit_return(span);
+
+        let state = self.functions.pop().unwrap();
+        let function = Constant::Function(Rc::new(state.function));
+        let constant = self.make_constant(function, name.span);
+        self.emit_with_constant(OpCode::Closure, constant, name.span);
+
+        for upvalue in state.upvalues {
+            self.emit_byte(upvalue.is_local as u8, name.span);
+            self.emit_byte(upvalue.index, name.span);
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[&Expr]) {
+        for argument in arguments {
+            self.visit_expr(argument);
+        }
+    }
+}
+
+impl<'a> ExprVisitor<'a, ()> for Compiler<'_> {
+    fn visit_expr(&mut self, expr: &Expr<'a>) {
+        match expr {
+            Expr::Assign { name, value } => {
+                self.visit_expr(value);
+                self.set_variable(&name.lexeme, name.span);
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+
+                let op = match operator.r#type {
+                    TokenType::BangEqual => OpCode::NotEqual,
+                    TokenType::EqualEqual => OpCode::Equal,
+                    TokenType::Greater => OpCode::Greater,
+                    TokenType::GreaterEqual => OpCode::GreaterEqual,
+                    TokenType::Less => OpCode::Less,
+                    TokenType::LessEqual => OpCode::LessEqual,
+                    TokenType::Plus => OpCode::Add,
+                    TokenType::Minus => OpCode::Subtract,
+                    TokenType::Star => OpCode::Multiply,
+                    TokenType::Slash => OpCode::Divide,
+                    _ => unreachable!("the parser only produces binary arithmetic and comparisons"),
+                };
+                self.emit_op(op, operator.span);
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let count = arguments.len() as u8;
+
+                // Calling a method straight away skips creating a bound
+                // method. Errors looking the method up point at its name,
+                // while errors calling it point at the parenthesis.
+                match callee {
+                    Expr::Get { object, name } => {
+                        self.visit_expr(object);
+                        self.arguments(arguments);
+
+                        let constant = self.identifier_constant(&name.lexeme, name.span);
+                        self.emit_with_constant(OpCode::Invoke, constant, name.span);
+                        self.emit_byte(count, paren.span);
+                    }
+                    Expr::Super { keyword, method } => {
+                        self.get_variable("this", keyword.span);
+                        self.arguments(arguments);
+                        self.get_variable("super", keyword.span);
+
+                        let constant = self.identifier_constant(&method.lexeme, method.span);
+                        self.emit_with_constant(OpCode::SuperInvoke, constant, method.span);
+                        self.emit_byte(count, paren.span);
+                    }
+                    _ => {
+                        self.visit_expr(callee);
+                        self.arguments(arguments);
+
+                        self.emit_op(OpCode::Call, paren.span);
+                        self.emit_byte(count, paren.span);
+                    }
+                }
+            }
+            Expr::Get { object, name } => {
+                self.visit_expr(object);
+
+                let constant = self.identifier_constant(&name.lexeme, name.span);
+                self.emit_with_constant(OpCode::GetProperty, constant, name.span);
+            }
+            Expr::Grouping { expression, .. } => self.visit_expr(expression),
+            Expr::Literal { value, span } => match value {
+                Literal::Bool(true) => self.emit_op(OpCode::True, *span),
+                Literal::Bool(false) => self.emit_op(OpCode::False, *span),
+                Literal::Nil => self.emit_op(OpCode::Nil, *span),
+                Literal::Number(number) => {
+                    let constant = self.make_constant(Constant::Number(*number), *span);
+                    self.emit_with_constant(OpCode::Constant, constant, *span);
+                }
+                Literal::String(string) => {
+                    let constant =
+                        self.make_constant(Constant::String(string.as_str().into()), *span);
+                    self.emit_with_constant(OpCode::Constant, constant, *span);
+                }
+            },
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.visit_expr(left);
+
+                // The left operand stays on the stack as the result when it
+                // decides the outcome.
+                if operator.r#type == TokenType::Or {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse, operator.span);
+                    let end_jump = self.emit_jump(OpCode::Jump, operator.span);
+
+                    self.patch_jump(else_jump, operator.span);
+                    self.emit_op(OpCode::Pop, operator.span);
+                    self.visit_expr(right);
+                    self.patch_jump(end_jump, operator.span);
+                } else {
+                    let end_jump = self.emit_jump(OpCode::JumpIfFalse, operator.span);
+
+                    self.emit_op(OpCode::Pop, operator.span);
+                    self.visit_expr(right);
+                    self.patch_jump(end_jump, operator.span);
+                }
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.visit_expr(object);
+                self.visit_expr(value);
+
+                let constant = self.identifier_constant(&name.lexeme, name.span);
+                self.emit_with_constant(OpCode::SetProperty, constant, name.span);
+            }
+            Expr::Super { keyword, method } => {
+                self.get_variable("this", keyword.span);
+                self.get_variable("super", keyword.span);
+
+                let constant = self.identifier_constant(&method.lexeme, method.span);
+                self.emit_with_constant(OpCode::GetSuper, constant, method.span);
+            }
+            Expr::This(keyword) => self.get_variable("this", keyword.span),
+            Expr::Unary { operator, right } => {
+                self.visit_expr(right);
+
+                let op = match operator.r#type {
+                    TokenType::Bang => OpCode::Not,
+                    TokenType::Minus => OpCode::Negate,
+                    _ => unreachable!("the parser only produces `!` and `-` as unary operators"),
+                };
+                self.emit_op(op, operator.span);
+            }
+            Expr::Variable(name) => self.get_variable(&name.lexeme, name.span),
+        }
+    }
+}
+
+impl<'a> StmtVisitor<'a, ()> for Compiler<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt<'a>) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                for statement in statements {
+                    self.visit_stmt(statement);
+                }
+
+                let span = self.chunk().last_span().unwrap_or_default();
+                self.end_scope(span);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let name_constant = self.identifier_constant(&name.lexeme, name.span);
+                let global = self.current().scope_depth == 0;
+
+                if !global {
+                    self.add_local(&name.lexeme, name);
+                }
+                self.emit_with_constant(OpCode::Class, name_constant, name.span);
+                if global {
+                    self.emit_with_constant(OpCode::DefineGlobal, name_constant, name.span);
+                }
+
+                // The superclass is kept in a local named `super` for the
+                // methods to capture.
+                if let Some(superclass) = superclass {
+                    let Expr::Variable(superclass_name) = superclass else {
+                        unreachable!("superclass is always parsed as a variable");
+                    };
+
+                    self.visit_expr(superclass);
+                    self.begin_scope();
+                    self.add_local("super", name);
+
+                    self.get_variable(&name.lexeme, name.span);
+                    let constant =
+                        self.identifier_constant(&superclass_name.lexeme, superclass_name.span);
+                    self.emit_with_constant(OpCode::Inherit, constant, superclass_name.span);
+                }
+
+                self.get_variable(&name.lexeme, name.span);
+                for method in methods {
+                    let kind = if method.name.lexeme == "init" {
+                        FunctionKind::Initializer
+                    } else {
+                        FunctionKind::Method
+                    };
+                    self.function(method, kind);
+
+                    let constant = self.identifier_constant(&method.name.lexeme, method.name.span);
+                    self.emit_with_constant(OpCode::Method, constant, method.name.span);
+                }
+                self.emit_op(OpCode::Pop, name.span);
+
+                if superclass.is_some() {
+                    self.end_scope(name.span);
+                }
+            }
+            Stmt::Expression(expression) => {
+                self.visit_expr(expression);
+                self.emit_op(OpCode::Pop, expression.span());
+            }
+            Stmt::Function(declaration) => {
+                // A local function is declared before its body is compiled so
+                // that it can call itself.
+                if self.current().scope_depth > 0 {
+                    self.add_local(&declaration.name.lexeme, declaration.name);
+                    self.function(declaration, FunctionKind::Function);
+                } else {
+                    self.function(declaration, FunctionKind::Function);
+                    self.define_variable(declaration.name);
+                }
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let span = condition.span();
+                self.visit_expr(condition);
+
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit_op(OpCode::Pop, span);
+                self.visit_stmt(then_branch);
+
+                let else_jump = self.emit_jump(OpCode::Jump, span);
+                self.patch_jump(then_jump, span);
+                self.emit_op(OpCode::Pop, span);
+
+                if let Some(else_branch) = else_branch {
+                    self.visit_stmt(else_branch);
+                }
+                self.patch_jump(else_jump, span);
+            }
+            Stmt::Print(expression) => {
+                self.visit_expr(expression);
+                self.emit_op(OpCode::Print, expression.span());
+            }
+            Stmt::Return { keyword, value } => match value {
+                Some(value) => {
+                    self.visit_expr(value);
+                    self.emit_op(OpCode::Return, keyword.span);
+                }
+                None => self.emit_return(keyword.span),
+            },
+            Stmt::Var { name, initializer } => {
+                match initializer {
+                    Some(initializer) => self.visit_expr(initializer),
+                    None => self.emit_op(OpCode::Nil, name.span),
+                }
+
+                self.define_variable(name);
+            }
+            Stmt::While { condition, body } => {
+                let span = condition.span();
+                let loop_start = self.chunk().code.len();
+                self.visit_expr(condition);
+
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit_op(OpCode::Pop, span);
+                self.visit_stmt(body);
+                self.emit_loop(loop_start, span);
+
+                self.patch_jump(exit_jump, span);
+                self.emit_op(OpCode::Pop, span);
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt::Write;
+
+use super::chunk::{Chunk, Constant, Function, OpCode};
+
+// Disassembles a compiled function and every function nested inside it, in
+// the format clox prints with DEBUG_PRINT_CODE:
+//
+//     == fib ==
+//     0000    1 OP_GET_LOCAL        1
+//     0002    | OP_CONSTANT         0 '2'
+//     0005    | OP_LESS
+pub fn disassemble(function: &Function) -> String {
+    let mut output = String::new();
+    disassemble_function(function, &mut output);
+    output
+}
+
+fn disassemble_function(function: &Function, output: &mut String) {
+    let name = match &function.name {
+        Some(name) => name.to_string(),
+        None => "<script>".to_string(),
+    };
+    writeln!(output, "== {name} ==").unwrap();
+
+    let chunk = &function.chunk;
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let (instruction, next) = disassemble_instruction(chunk, offset);
+        writeln!(output, "{instruction}").unwrap();
+        offset = next;
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(function) = constant {
+            writeln!(output).unwrap();
+            disassemble_function(function, output);
+        }
+    }
+}
+
+// Disassembles the instruction at `offset`, returning it along with the
+// offset of the next instruction. Closures take several lines, one for each
+// variable they capture.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let mut output = format!("{offset:04} ");
+
+    let line = chunk.span_at(offset).line;
+    if offset > 0 && chunk.span_at(offset - 1).line == line {
+        output.push_str("   | ");
+    } else {
+        write!(output, "{line:4} ").unwrap();
+    }
+
+    let byte = chunk.code[offset];
+    let Some(op) = OpCode::from_byte(byte) else {
+        write!(output, "Unknown opcode {byte}").unwrap();
+        return (output, offset + 1);
+    };
+
+    let name = op_name(op);
+    let next = match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Inherit
+        | OpCode::Method => {
+            let constant = chunk.read_u16(offset + 1);
+            write!(
+                output,
+                "{name:<16} {constant:4} '{}'",
+                chunk.constants[constant as usize]
+            )
+            .unwrap();
+            offset + 3
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => {
+            let operand = chunk.code[offset + 1];
+            write!(output, "{name:<16} {operand:4}").unwrap();
+            offset + 2
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let target = if op == OpCode::Loop {
+                offset + 3 - jump
+            } else {
+                offset + 3 + jump
+            };
+            write!(output, "{name:<16} {offset:4} -> {target}").unwrap();
+            offset + 3
+        }
+        OpCode::Invoke | OpCode::SuperInvoke => {
+            let constant = chunk.read_u16(offset + 1);
+            let arguments = chunk.code[offset + 3];
+            write!(
+                output,
+                "{name:<16} ({arguments} args) {constant:4} '{}'",
+                chunk.constants[constant as usize]
+            )
+            .unwrap();
+            offset + 4
+        }
+        OpCode::Closure => {
+            let constant = chunk.read_u16(offset + 1);
+            let function = &chunk.constants[constant as usize];
+            write!(output, "{name:<16} {constant:4} {function}").unwrap();
+
+            let mut offset = offset + 3;
+            if let Constant::Function(function) = function {
+                for _ in 0..function.upvalue_count {
+                    let kind = if chunk.code[offset] == 1 {
+                        "local"
+                    } else {
+                        "upvalue"
+                    };
+                    let index = chunk.code[offset + 1];
+                    write!(
+                        output,
+                        "\n{offset:04}    |                     {kind} {index}"
+                    )
+                    .unwrap();
+                    offset += 2;
+                }
+            }
+            offset
+        }
+        OpCode::Nil
+        | OpCode::True
+        | OpCode::False
+        | OpCode::Pop
+        | OpCode::Equal
+        | OpCode::NotEqual
+        | OpCode::Greater
+        | OpCode::GreaterEqual
+        | OpCode::Less
+        | OpCode::LessEqual
+        | OpCode::Add
+        | OpCode::Subtract
+        | OpCode::Multiply
+        | OpCode::Divide
+        | OpCode::Not
+        | OpCode::Negate
+        | OpCode::Print
+        | OpCode::CloseUpvalue
+        | OpCode::Return => {
+            output.push_str(name);
+            offset + 1
+        }
+    };
+
+    (output, next)
+}
+
+// The names clox gives its opcodes, so that dumps can be compared with it.
+fn op_name(op: OpCode) -> &'static str {
+    match op {
+        OpCode::Constant => "OP_CONSTANT",
+        OpCode::Nil => "OP_NIL",
+        OpCode::True => "OP_TRUE",
+        OpCode::False => "OP_FALSE",
+        OpCode::Pop => "OP_POP",
+        OpCode::GetLocal => "OP_GET_LOCAL",
+        OpCode::SetLocal => "OP_SET_LOCAL",
+        OpCode::GetGlobal => "OP_GET_GLOBAL",
+        OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+        OpCode::SetGlobal => "OP_SET_GLOBAL",
+        OpCode::GetUpvalue => "OP_GET_UPVALUE",
+        OpCode::SetUpvalue => "OP_SET_UPVALUE",
+        OpCode::GetProperty => "OP_GET_PROPERTY",
+        OpCode::SetProperty => "OP_SET_PROPERTY",
+        OpCode::GetSuper => "OP_GET_SUPER",
+        OpCode::Equal => "OP_EQUAL",
+        OpCode::NotEqual => "OP_NOT_EQUAL",
+        OpCode::Greater => "OP_GREATER",
+        OpCode::GreaterEqual => "OP_GREATER_EQUAL",
+        OpCode::Less => "OP_LESS",
+        OpCode::LessEqual => "OP_LESS_EQUAL",
+        OpCode::Add => "OP_ADD",
+        OpCode::Subtract => "OP_SUBTRACT",
+        OpCode::Multiply => "OP_MULTIPLY",
+        OpCode::Divide => "OP_DIVIDE",
+        OpCode::Not => "OP_NOT",
+        OpCode::Negate => "OP_NEGATE",
+        OpCode::Print => "OP_PRINT",
+        OpCode::Jump => "OP_JUMP",
+        OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+        OpCode::Loop => "OP_LOOP",
+        OpCode::Call => "OP_CALL",
+        OpCode::Invoke => "OP_INVOKE",
+        OpCode::SuperInvoke => "OP_SUPER_INVOKE",
+        OpCode::Closure => "OP_CLOSURE",
+        OpCode::CloseUpvalue => "OP_CLOSE_UPVALUE",
+        OpCode::Return => "OP_RETURN",
+        OpCode::Class => "OP_CLASS",
+        OpCode::Inherit => "OP_INHERIT",
+        OpCode::Method => "OP_METHOD",
+    }
+}
@@ -0,0 +1,152 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{
+    heap::{Trace, Tracer},
+    interpreter::RuntimeError,
+};
+
+use super::{chunk::Function, value::Value};
+
+// A function together with the variables it captured from the functions
+// enclosing it.
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+// A captured variable. It points at the variable's slot on the stack while
+// the function that declared it is running, and holds the value itself once
+// that function has returned.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Class {
+    pub name: Rc<str>,
+    pub methods: HashMap<Rc<str>, Rc<Closure>>,
+}
+
+impl Class {
+    pub fn new(name: Rc<str>) -> Class {
+        Class {
+            name,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<RefCell<Class>>,
+    pub fields: HashMap<Rc<str>, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<RefCell<Class>>) -> Instance {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+// A method pulled off an instance, which remembers the instance to use as
+// `this` when it is called.
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, RuntimeError>;
+
+pub struct Native {
+    pub arity: usize,
+    pub function: Box<NativeFn>,
+}
+
+impl Trace<'static> for Closure {
+    fn trace(&self, tracer: &mut Tracer) {
+        for upvalue in &self.upvalues {
+            tracer.object(upvalue);
+        }
+    }
+
+    // Every cycle through a closure also runs through one of its upvalues,
+    // and clearing those is enough to break it.
+    fn clear(&self) {}
+}
+
+impl Trace<'static> for RefCell<Upvalue> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(upvalue) = self.try_borrow() else {
+            tracer.opaque();
+            return;
+        };
+
+        if let Upvalue::Closed(value) = &*upvalue {
+            value.trace(tracer);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut upvalue) = self.try_borrow_mut() {
+            *upvalue = Upvalue::Closed(Value::Nil);
+        }
+    }
+}
+
+impl Trace<'static> for RefCell<Class> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(class) = self.try_borrow() else {
+            tracer.opaque();
+            return;
+        };
+
+        for method in class.methods.values() {
+            tracer.object(method);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut class) = self.try_borrow_mut() {
+            class.methods.clear();
+        }
+    }
+}
+
+impl Trace<'static> for RefCell<Instance> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(instance) = self.try_borrow() else {
+            tracer.opaque();
+            return;
+        };
+
+        tracer.object(&instance.class);
+        for value in instance.fields.values() {
+            value.trace(tracer);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut instance) = self.try_borrow_mut() {
+            instance.fields.clear();
+        }
+    }
+}
+
+impl Trace<'static> for BoundMethod {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.receiver.trace(tracer);
+        tracer.object(&self.method);
+    }
+
+    // The receiver and method are fixed, so cycles through a bound method
+    // are broken at the instance or upvalue that holds on to it.
+    fn clear(&self) {}
+}
@@ -0,0 +1,115 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use crate::{
+    heap::Tracer,
+    interpreter::{Interpreter, RuntimeError},
+    literal::Literal,
+};
+
+use super::object::{BoundMethod, Class, Closure, Instance, Native};
+
+// A value on the VM's stack. Strings and objects are reference counted, the
+// same way the tree-walk interpreter's values are.
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Closure(Rc<Closure>),
+    Class(Rc<RefCell<Class>>),
+    Instance(Rc<RefCell<Instance>>),
+    BoundMethod(Rc<BoundMethod>),
+    Native(Rc<Native>),
+}
+
+impl Value {
+    // Matches the names the tree-walk interpreter uses in error notes.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Closure(_) | Value::BoundMethod(_) => "a function",
+            Value::Class(_) => "a class",
+            Value::Instance(_) => "an instance",
+            Value::Native(_) => "a native function",
+        }
+    }
+
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Value::Closure(closure) => tracer.object(closure),
+            Value::Class(class) => tracer.object(class),
+            Value::Instance(instance) => tracer.object(instance),
+            Value::BoundMethod(bound) => tracer.object(bound),
+            _ => {}
+        }
+    }
+}
+
+impl From<Literal> for Value {
+    fn from(literal: Literal) -> Self {
+        match literal {
+            Literal::Bool(bool) => Value::Bool(bool),
+            Literal::Number(number) => Value::Number(number),
+            Literal::String(string) => Value::String(string.into()),
+            Literal::Nil => Value::Nil,
+        }
+    }
+}
+
+impl TryFrom<&Value> for Literal {
+    type Error = RuntimeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(bool) => Ok(Literal::Bool(*bool)),
+            Value::Number(number) => Ok(Literal::Number(*number)),
+            Value::String(string) => Ok(Literal::String(string.to_string())),
+            Value::Nil => Ok(Literal::Nil),
+            _ => Err(RuntimeError::not_a_literal(value.type_name())),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Closure(left), Value::Closure(right)) => Rc::ptr_eq(left, right),
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            (Value::BoundMethod(left), Value::BoundMethod(right)) => Rc::ptr_eq(left, right),
+            (Value::Native(left), Value::Native(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+// Prints values exactly like the tree-walk interpreter's `print` does.
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Number(number) => write!(f, "{}", Interpreter::stringify_number(*number)),
+            Value::String(string) => write!(f, "{string}"),
+            Value::Closure(closure) => write!(f, "{closure}"),
+            Value::Class(class) => write!(f, "{}", class.borrow().name),
+            Value::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.borrow().name)
+            }
+            Value::BoundMethod(bound) => write!(f, "{}", bound.method),
+            Value::Native(_) => write!(f, "<native fn>"),
+        }
+    }
+}
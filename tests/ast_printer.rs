@@ -0,0 +1,39 @@
+// Checks the S-expressions the REPL's `:ast` command shows.
+
+use rusty_lox::{AstPrinter, Diagnostics, Parser, Scanner};
+use typed_arena::Arena;
+
+fn print_ast(source: &str) -> String {
+    let mut diagnostics = Diagnostics::new();
+    let tokens = Scanner::new(source.as_bytes().to_vec()).scan_tokens(&mut diagnostics);
+
+    let arena = Arena::new();
+    let statements = Parser::new(&mut diagnostics, &tokens).parse(&arena).unwrap();
+    assert!(diagnostics.is_empty());
+
+    AstPrinter::new().print(&statements)
+}
+
+#[test]
+fn prints_expressions() {
+    assert_eq!(
+        print_ast("print -1 + 2 * (3 - x);"),
+        "(print (+ (- 1.0) (* 2.0 (group (- 3.0 x)))))"
+    );
+    assert_eq!(
+        print_ast("a.b = c or f(\"d\", nil);"),
+        "(; (= a b (or c (call f \"d\" nil))))"
+    );
+}
+
+#[test]
+fn prints_statements() {
+    assert_eq!(
+        print_ast("var a; while (a) { a = false; }"),
+        "(var a)\n(while a (block (; (= a false))))"
+    );
+    assert_eq!(
+        print_ast("class B < A { m(x) { return super.m(x); } }"),
+        "(class B < A (fun m(x) (return (call (super m) x))))"
+    );
+}
@@ -0,0 +1,174 @@
+// Checks that scripts survive a round trip through the `.loxc` format, and
+// that files which can't be trusted are rejected.
+
+use rusty_lox::{Arenas, Diagnostics, ErrorCode, ErrorFormat, LoadError, Lox, FORMAT_VERSION};
+
+const SCRIPT: &[u8] = b"fun f() {\n  return 1 + nil;\n}\nf();\n";
+
+fn compile(source: &[u8]) -> Vec<u8> {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let (diagnostics, bytes) = lox.compile_bytecode(source.to_vec());
+    assert!(diagnostics.is_empty());
+    bytes.unwrap()
+}
+
+fn render(lox: &Lox, diagnostics: &Diagnostics) -> String {
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.render(ErrorFormat::Rich, "script.lox", lox.source()))
+        .collect()
+}
+
+#[test]
+fn runs_like_the_source() {
+    let bytes = compile(SCRIPT);
+
+    let arenas = Arenas::new();
+    let mut from_source = Lox::new(&arenas);
+    let expected = from_source.run(SCRIPT.to_vec());
+
+    let arenas = Arenas::new();
+    let mut from_bytecode = Lox::new(&arenas);
+    let diagnostics = from_bytecode.run_bytecode(&bytes).unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        render(&from_bytecode, &diagnostics),
+        render(&from_source, &expected)
+    );
+}
+
+#[test]
+fn moves_spans_after_earlier_source() {
+    let bytes = compile(SCRIPT);
+
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+    lox.run(b"var a = 1;\nvar b = 2;".to_vec());
+
+    let diagnostics = lox.run_bytecode(&bytes).unwrap();
+    let rendered = render(&lox, &diagnostics);
+
+    assert!(rendered.contains(" --> script.lox:4:12\n"), "{rendered}");
+    assert!(rendered.contains("4 |   return 1 + nil;\n"), "{rendered}");
+}
+
+#[test]
+fn rejects_other_files() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let result = lox.run_bytecode(b"print 1;");
+    assert_eq!(result.err(), Some(LoadError::NotBytecode));
+}
+
+#[test]
+fn rejects_other_format_versions() {
+    let mut bytes = compile(SCRIPT);
+    bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let error = lox.run_bytecode(&bytes).unwrap_err();
+    assert_eq!(error, LoadError::UnsupportedVersion(FORMAT_VERSION + 1));
+    assert!(error.to_string().contains("compile the script again"));
+}
+
+#[test]
+fn rejects_corrupt_files() {
+    let bytes = compile(SCRIPT);
+
+    let mut flipped = bytes.clone();
+    let last = flipped.len() - 1;
+    flipped[last] ^= 1;
+
+    let truncated = &bytes[..bytes.len() - 1];
+
+    for bytes in [&flipped[..], truncated] {
+        let arenas = Arenas::new();
+        let mut lox = Lox::new(&arenas);
+
+        let result = lox.run_bytecode(bytes);
+        assert_eq!(result.err(), Some(LoadError::ChecksumMismatch));
+    }
+
+    // A file edited by hand, or written by a broken compiler, passes the
+    // checksum as long as it is recomputed. The script's code is
+    // `OP_CONSTANT 0 0`, `OP_PRINT`, `OP_NIL`, `OP_RETURN`.
+    let source = b"print 1;";
+    let bytes = compile(source);
+    let code = 10 + 4 + source.len() + 1 + 1 + 2 + 4;
+
+    let cases = [
+        (code + 1, 200, "constant out of range"),
+        (code, 250, "unknown opcode"),
+        (code + 4, 4, "instruction pops an empty stack"),
+    ];
+
+    for (index, byte, reason) in cases {
+        let mut edited = bytes.clone();
+        edited[index] = byte;
+        let checksum = crc32(&edited[10..]);
+        edited[6..10].copy_from_slice(&checksum.to_le_bytes());
+
+        let arenas = Arenas::new();
+        let mut lox = Lox::new(&arenas);
+
+        let result = lox.run_bytecode(&edited);
+        assert_eq!(result.err(), Some(LoadError::Corrupt(reason)));
+    }
+}
+
+#[test]
+fn stops_code_that_misuses_values() {
+    // The verifier only knows how deep the stack is, not what is on it. The
+    // script's code is `OP_CONSTANT 0 0`, `OP_PRINT`, `OP_NIL`, `OP_RETURN`,
+    // with the string "x" as constant 0.
+    let source = b"print \"x\";";
+    let bytes = compile(source);
+    let code = 10 + 4 + source.len() + 1 + 1 + 2 + 4;
+
+    let cases: [[u8; 4]; 2] = [
+        // `OP_NIL`, then `OP_GET_SUPER 0 0` with nil as the superclass.
+        [1, 14, 0, 0],
+        // `OP_NIL`, then `OP_METHOD 0 0` defining nil on the script.
+        [1, 39, 0, 0],
+    ];
+
+    for instructions in cases {
+        let mut edited = bytes.clone();
+        edited[code..code + 4].copy_from_slice(&instructions);
+        let checksum = crc32(&edited[10..]);
+        edited[6..10].copy_from_slice(&checksum.to_le_bytes());
+
+        let arenas = Arenas::new();
+        let mut lox = Lox::new(&arenas);
+
+        let diagnostics = lox.run_bytecode(&edited).unwrap();
+        let codes: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect();
+        assert_eq!(codes, [ErrorCode::InvalidBytecode]);
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,134 @@
+// Drives the library directly and inspects the diagnostics it hands back,
+// without going through the CLI or capturing stderr.
+
+use std::collections::HashSet;
+
+use rusty_lox::{Arenas, Diagnostic, ErrorCode, ErrorFormat, Lox, Phase, Span, StackFrame};
+
+#[test]
+fn collects_every_compile_error() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let diagnostics = lox.run(b"var a = @;\nprint 1 2;".to_vec());
+
+    assert!(diagnostics.had_error());
+    assert!(!diagnostics.had_runtime_error());
+
+    let reported: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.code, diagnostic.message.as_str(), diagnostic.span))
+        .collect();
+
+    assert_eq!(
+        reported,
+        [
+            (ErrorCode::UnexpectedCharacter, "Unexpected character.", Span::new(8, 1, 1, 9)),
+            (ErrorCode::ExpectExpression, "Expect expression.", Span::new(9, 1, 1, 10)),
+            (ErrorCode::ExpectSemicolon, "Expect ';' after value.", Span::new(19, 1, 2, 9)),
+        ]
+    );
+    assert_eq!(diagnostics.iter().next().unwrap().phase, Phase::Scan);
+}
+
+#[test]
+fn returns_runtime_errors_and_keeps_the_session_usable() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let diagnostics = lox.run(b"var a = 1;\na();".to_vec());
+
+    assert!(!diagnostics.had_error());
+    assert!(diagnostics.had_runtime_error());
+    assert_eq!(diagnostics.len(), 1);
+
+    let error = diagnostics.iter().next().unwrap();
+    assert_eq!(error.phase, Phase::Runtime);
+    assert_eq!(error.code, ErrorCode::NotCallable);
+    assert_eq!(error.message, "Can only call functions and classes.");
+    assert_eq!(error.span.line, 2);
+
+    assert!(lox.run(b"a = a + 1;".to_vec()).is_empty());
+}
+
+#[test]
+fn runtime_errors_carry_the_call_stack() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let source = b"fun inner() {\n  return nil + 1;\n}\nfun outer() {\n  inner();\n}\nouter();";
+    let diagnostics = lox.run(source.to_vec());
+    let error = diagnostics.iter().next().unwrap();
+
+    let frame = |function: Option<&str>, line| StackFrame {
+        function: function.map(str::to_string),
+        line,
+    };
+
+    assert_eq!(
+        error.trace,
+        [frame(Some("inner"), 2), frame(Some("outer"), 5), frame(None, 7)]
+    );
+}
+
+#[test]
+fn error_codes_are_unique_and_round_trip() {
+    let codes: HashSet<_> = ErrorCode::ALL.iter().map(|code| code.code()).collect();
+    assert_eq!(codes.len(), ErrorCode::ALL.len());
+
+    for code in ErrorCode::ALL {
+        assert_eq!(code.code().parse::<ErrorCode>(), Ok(code));
+        assert!(!code.explanation().is_empty());
+    }
+
+    assert_eq!("L0003".parse::<ErrorCode>(), Ok(ErrorCode::ExpectExpression));
+    assert!("L9999".parse::<ErrorCode>().is_err());
+}
+
+#[test]
+fn spans_continue_between_runs_until_a_reset() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    lox.run(b"var a = 1;\nvar b = 2;".to_vec());
+    let diagnostics = lox.run(b"print c;".to_vec());
+    assert_eq!(diagnostics.iter().next().unwrap().span, Span::new(28, 1, 3, 7));
+
+    lox.reset();
+    let diagnostics = lox.run(b"print c;".to_vec());
+    assert_eq!(diagnostics.iter().next().unwrap().span, Span::new(6, 1, 1, 7));
+    assert_eq!(lox.source(), "print c;");
+}
+
+#[test]
+fn renders_errors_at_the_end_of_the_file() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let diagnostics = lox.run(b"fun f() {\n".to_vec());
+    let diagnostic = diagnostics.iter().next().unwrap();
+
+    assert_eq!(
+        diagnostic.render(ErrorFormat::Rich, "eof.lox", lox.source()),
+        "\
+error[L0006]: Expect '}' after block.
+ --> eof.lox:2:1
+  |
+2 |
+  | ^ at end
+
+"
+    );
+}
+
+#[test]
+fn renders_errors_without_a_location() {
+    let diagnostic = Diagnostic::new(
+        ErrorCode::ExpectExpression,
+        "Expect expression.",
+        Span::default(),
+    );
+    let rendered = diagnostic.render(ErrorFormat::Rich, "script.lox", "print 1;");
+
+    assert_eq!(rendered, "error[L0003]: Expect expression.\n --> script.lox:0:0\n\n");
+}
@@ -0,0 +1,51 @@
+// Checks the output and exit codes of `rusty-lox --explain`.
+
+use std::process::Command;
+
+use rusty_lox::ErrorCode;
+
+fn explain(code: &str) -> (String, String, i32) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rusty-lox"))
+        .args(["--explain", code])
+        .output()
+        .expect("failed to run rusty-lox");
+
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code().unwrap_or(-1),
+    )
+}
+
+#[test]
+fn prints_the_explanation() {
+    let (stdout, stderr, code) = explain("L0003");
+
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        format!("{}\n", ErrorCode::ExpectExpression.explanation())
+    );
+    assert!(stdout.contains("var total = 1 + ;"));
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn accepts_lowercase_codes() {
+    let (stdout, _, code) = explain("l0003");
+
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        format!("{}\n", ErrorCode::ExpectExpression.explanation())
+    );
+}
+
+#[test]
+fn rejects_unknown_codes() {
+    let (stdout, stderr, code) = explain("L0099");
+
+    assert_eq!(code, 64);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "Unknown error code 'L0099'.\n");
+}
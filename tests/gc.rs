@@ -0,0 +1,75 @@
+// Checks that the garbage collector frees cycles that reference counting
+// can't, while keeping everything the program can still reach.
+
+use std::{cell::RefCell, rc::Rc};
+
+use rusty_lox::{Arenas, GcConfig, Lox};
+
+#[test]
+fn collects_unreachable_cycles() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    // The instance holds a method bound to itself.
+    lox.run(b"class A { m() {} } { var a = A(); a.m = a.m; }".to_vec());
+
+    let before = lox.interpreter().gc_stats();
+    lox.interpreter().collect_garbage();
+    let after = lox.interpreter().gc_stats();
+
+    assert_eq!(after.collections, before.collections + 1);
+    assert!(after.objects_collected > before.objects_collected);
+    assert!(after.bytes_allocated < before.bytes_allocated);
+}
+
+#[test]
+fn keeps_reachable_objects() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    lox.run(b"class A { init() { this.me = this; } } var a = A(); fun counter() { var n = 0; fun inc() { n = n + 1; return n; } return inc; } var c = counter();".to_vec());
+    lox.interpreter().collect_garbage();
+
+    assert!(lox.run(b"c(); print c(); print a.me.me;".to_vec()).is_empty());
+    assert_eq!(lox.interpreter().gc_stats().objects_collected, 0);
+}
+
+#[test]
+fn stress_mode_collects_on_every_allocation() {
+    let arenas = Arenas::new();
+    let config = GcConfig {
+        stress: true,
+        log: false,
+    };
+    let mut lox = Lox::with_gc(&arenas, config);
+
+    let diagnostics = lox.run(b"fun f(n) { if (n < 2) return n; return f(n - 1) + f(n - 2); } var x = f(10);".to_vec());
+    assert!(diagnostics.is_empty());
+
+    let stats = lox.interpreter().gc_stats();
+    assert!(stats.collections > 100);
+    assert!(stats.total_bytes_allocated > stats.bytes_allocated);
+
+    // Resetting the session keeps the collector's configuration.
+    lox.reset();
+    assert_eq!(lox.interpreter().gc_config(), config);
+}
+
+#[test]
+fn logs_collections_to_the_host() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let arenas = Arenas::new();
+    let config = GcConfig {
+        stress: false,
+        log: true,
+    };
+    let mut lox = Lox::with_gc(&arenas, config).with_log(log.clone());
+
+    lox.run(b"class A { m() {} } { var a = A(); a.m = a.m; }".to_vec());
+    lox.interpreter().collect_garbage();
+
+    let log = String::from_utf8(log.borrow().clone()).unwrap();
+    assert!(log.starts_with("-- gc begin\n-- gc end\n   collected "), "{log}");
+    assert!(log.ends_with(" unreachable objects\n"), "{log}");
+}
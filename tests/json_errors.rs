@@ -0,0 +1,42 @@
+// Checks the `--error-format=json` output, which tools parse line by line, so
+// the exact shape of each object matters.
+
+use std::process::Command;
+
+fn json_errors(script: &str) -> (Vec<String>, i32) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rusty-lox"))
+        .arg("--error-format=json")
+        .arg(script)
+        .output()
+        .expect("failed to run rusty-lox");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines = stderr.lines().map(str::to_string).collect();
+
+    (lines, output.status.code().unwrap_or(-1))
+}
+
+#[test]
+fn reports_compile_errors_as_json() {
+    let (lines, code) = json_errors("test/unexpected_character.lox");
+
+    assert_eq!(code, 65);
+    assert_eq!(
+        lines,
+        [
+            r#"{"severity":"error","code":"L0001","message":"Unexpected character.","file":"test/unexpected_character.lox","span":{"offset":106,"length":1,"line":3,"column":7},"lexeme":"|","label":null,"notes":[],"stack":[]}"#,
+            r#"{"severity":"error","code":"L0005","message":"Expect ')' after arguments.","file":"test/unexpected_character.lox","span":{"offset":108,"length":1,"line":3,"column":9},"lexeme":"b","label":"at 'b'","notes":[],"stack":[]}"#,
+        ]
+    );
+}
+
+#[test]
+fn reports_runtime_errors_as_json() {
+    let (lines, code) = json_errors("test/operator/negate_nonnum.lox");
+
+    assert_eq!(code, 70);
+    assert_eq!(
+        lines,
+        [r#"{"severity":"error","code":"L0020","message":"Operand must be a number.","file":"test/operator/negate_nonnum.lox","span":{"offset":0,"length":1,"line":1,"column":1},"lexeme":"-","label":null,"notes":["the operand is a string"],"stack":[{"function":null,"line":1}]}"#]
+    );
+}
@@ -0,0 +1,209 @@
+// Runs every `.lox` file under `test/` through the interpreter and checks its
+// output against the expectations written in the file's comments, following
+// the conventions of the Crafting Interpreters test suite:
+//
+//     print 1; // expect: 1
+//     1 + nil; // expect runtime error: Operands must be two numbers or two strings.
+//     return;  // Error at 'return': Can't return from top-level code.
+//     // [line 3] Error: Unexpected character.
+//
+// Expectations tagged `[c line N]` only apply to clox and are ignored. Errors
+// are compared in the jlox format, so the interpreter is run with
+// `--error-format=jlox`. The suite runs against both the tree-walk
+// interpreter and the bytecode VM, and for each again with `--gc-stress`, so
+// that a collection happens at every allocation. It also runs with every
+// script first compiled to a `.loxc` file, to check nothing is lost in the
+// round trip.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+#[derive(Default)]
+struct Expectations {
+    output: Vec<String>,
+    errors: Vec<String>,
+    runtime_error: Option<(String, usize)>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Expectations {
+        let mut expectations = Expectations::default();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+
+            if let Some(output) = after(line, "// expect: ") {
+                expectations.output.push(output.to_string());
+            } else if let Some(message) = after(line, "// expect runtime error: ") {
+                expectations.runtime_error = Some((message.to_string(), line_number));
+            } else if let Some(error) = after(line, "// [line ") {
+                expectations.errors.push(format!("[line {error}"));
+            } else if let Some(error) = after(line, "// [java line ") {
+                expectations.errors.push(format!("[line {error}"));
+            } else if let Some(error) = after(line, "// Error") {
+                expectations
+                    .errors
+                    .push(format!("[line {line_number}] Error{error}"));
+            }
+        }
+
+        expectations
+    }
+
+    fn exit_code(&self) -> i32 {
+        if !self.errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+}
+
+fn after<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    line.find(marker).map(|index| &line[index + marker.len()..])
+}
+
+fn collect_tests(directory: &Path, tests: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            collect_tests(&path, tests);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            tests.push(path);
+        }
+    }
+}
+
+fn run_source(path: &Path, flags: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rusty-lox"))
+        .arg("--error-format=jlox")
+        .args(flags)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+// Compiles the script to a `.loxc` file and runs that, unless compiling it
+// failed.
+fn run_compiled(path: &Path, flags: &[&str]) -> Output {
+    let name = path.to_string_lossy().replace(['/', '\\'], "_");
+    let compiled = env::temp_dir().join(format!("rusty_lox_{name}c"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rusty-lox"))
+        .arg("--error-format=jlox")
+        .arg("compile")
+        .arg(path)
+        .arg("-o")
+        .arg(&compiled)
+        .output()
+        .unwrap();
+
+    if !output.status.success() {
+        return output;
+    }
+
+    let output = run_source(&compiled, flags);
+    fs::remove_file(&compiled).unwrap();
+    output
+}
+
+fn run_test(path: &Path, run: fn(&Path, &[&str]) -> Output, flags: &[&str]) -> Vec<String> {
+    let source = fs::read_to_string(path).unwrap();
+    let expected = Expectations::parse(&source);
+
+    let output = run(path, flags);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr: Vec<&str> = stderr.lines().collect();
+
+    let mut failures = vec![];
+
+    let stdout: Vec<&str> = stdout.lines().collect();
+    if stdout != expected.output {
+        failures.push(format!(
+            "Expected output {:?} but got {:?}.",
+            expected.output, stdout
+        ));
+    }
+
+    if let Some((message, line)) = &expected.runtime_error {
+        let expected_stderr = [message.clone(), format!("[line {line}]")];
+        if stderr.len() < 2 || stderr[..2] != expected_stderr {
+            failures.push(format!(
+                "Expected runtime error {:?} but got {:?}.",
+                expected_stderr, stderr
+            ));
+        }
+    } else if stderr != expected.errors {
+        failures.push(format!(
+            "Expected errors {:?} but got {:?}.",
+            expected.errors, stderr
+        ));
+    }
+
+    let exit_code = output.status.code();
+    if exit_code != Some(expected.exit_code()) {
+        failures.push(format!(
+            "Expected exit code {} but got {:?}.",
+            expected.exit_code(),
+            exit_code
+        ));
+    }
+
+    failures
+}
+
+fn run_suite(run: fn(&Path, &[&str]) -> Output, flags: &[&str]) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+
+    let mut tests = vec![];
+    collect_tests(&root, &mut tests);
+    tests.sort();
+
+    let mut failed = 0;
+    for test in &tests {
+        let failures = run_test(test, run, flags);
+
+        if !failures.is_empty() {
+            failed += 1;
+            eprintln!("FAIL {}", test.strip_prefix(&root).unwrap().display());
+            for failure in failures {
+                eprintln!("    {failure}");
+            }
+        }
+    }
+
+    assert_eq!(failed, 0, "{failed} of {} Lox tests failed", tests.len());
+}
+
+#[test]
+fn lox_test_suite() {
+    run_suite(run_source, &[]);
+}
+
+#[test]
+fn lox_test_suite_under_gc_stress() {
+    run_suite(run_source, &["--gc-stress"]);
+}
+
+#[test]
+fn lox_test_suite_on_vm() {
+    run_suite(run_source, &["--backend=vm"]);
+}
+
+#[test]
+fn lox_test_suite_on_vm_under_gc_stress() {
+    run_suite(run_source, &["--backend=vm", "--gc-stress"]);
+}
+
+#[test]
+fn lox_test_suite_from_bytecode() {
+    run_suite(run_compiled, &[]);
+}
@@ -0,0 +1,45 @@
+// Runtime values are reference counted, so they should be freed as soon as
+// the program stops referring to them instead of living as long as the
+// interpreter.
+
+use std::rc::Rc;
+
+use rusty_lox::{Arenas, Lox, Value};
+
+fn global<'a>(lox: &mut Lox<'a>, name: &str) -> Value<'a> {
+    lox.interpreter()
+        .bindings()
+        .into_iter()
+        .find(|(binding, _)| binding == name)
+        .map(|(_, value)| value)
+        .unwrap()
+}
+
+#[test]
+fn unreachable_instances_are_released() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    lox.run(b"class A {} var a = A(); var b = a;".to_vec());
+
+    let Value::Instance(instance) = global(&mut lox, "a") else {
+        panic!("expected an instance");
+    };
+    assert_eq!(Rc::strong_count(&instance), 3);
+
+    lox.run(b"a = nil; b = nil;".to_vec());
+    assert_eq!(Rc::strong_count(&instance), 1);
+}
+
+#[test]
+fn temporaries_are_not_kept_alive_by_loops() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    lox.run(b"class A {} var last; for (var i = 0; i < 1000; i = i + 1) { last = A(); }".to_vec());
+
+    let Value::Instance(instance) = global(&mut lox, "last") else {
+        panic!("expected an instance");
+    };
+    assert_eq!(Rc::strong_count(&instance), 2);
+}
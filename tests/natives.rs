@@ -0,0 +1,163 @@
+// Checks that functions written in Rust can be called from Lox code.
+
+use std::{cell::RefCell, rc::Rc};
+
+use rusty_lox::{Arenas, Backend, ErrorCode, Literal, Lox, RuntimeError, Span, Value};
+
+fn lox_with_output<'a>(
+    arenas: &'a Arenas<'a>,
+    backend: Backend,
+) -> (Lox<'a>, Rc<RefCell<Vec<u8>>>) {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let lox = Lox::new(arenas)
+        .with_backend(backend)
+        .with_output(output.clone());
+
+    (lox, output)
+}
+
+#[test]
+fn interpreter_calls_host_closures() {
+    let arenas = Arenas::new();
+    let (mut lox, output) = lox_with_output(&arenas, Backend::Tree);
+
+    let total = Rc::new(RefCell::new(0.0));
+    let sum = total.clone();
+    lox.interpreter().define_native("add", 1, move |arguments| {
+        let Value::Number(number) = arguments[0] else {
+            return Err(RuntimeError::from_native("Expected a number."));
+        };
+
+        *sum.borrow_mut() += number;
+        Ok(Value::Number(*sum.borrow()))
+    });
+
+    let diagnostics = lox.run(b"add(1); print add(2);".to_vec());
+    assert!(diagnostics.is_empty());
+    assert_eq!(*total.borrow(), 3.0);
+    assert_eq!(output.borrow().as_slice(), b"3\n");
+}
+
+#[test]
+fn interpreter_checks_the_arity_of_natives() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+    lox.interpreter()
+        .define_native("pair", 2, |arguments| Ok(arguments[0].clone()));
+
+    let diagnostics = lox.run(b"pair(1);".to_vec());
+    let error = diagnostics.iter().next().unwrap();
+
+    assert_eq!(error.code, ErrorCode::WrongArity);
+    assert_eq!(error.message, "Expected 2 arguments but got 1.");
+}
+
+#[test]
+fn interpreter_reports_errors_from_natives_at_the_call() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+    lox.interpreter().define_native("fail", 0, |_| {
+        Err(RuntimeError::from_native("Something went wrong."))
+    });
+
+    let diagnostics = lox.run(b"var a = 1;\nprint fail();".to_vec());
+    let error = diagnostics.iter().next().unwrap();
+
+    assert_eq!(error.code, ErrorCode::NativeFunctionFailed);
+    assert_eq!(error.message, "Something went wrong.");
+    // Natives have no span of their own, so the error points at the closing
+    // parenthesis of the call.
+    assert_eq!(error.span, Span::new(22, 1, 2, 12));
+}
+
+#[test]
+fn lox_defines_natives_on_both_backends() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let arenas = Arenas::new();
+        let (mut lox, output) = lox_with_output(&arenas, backend);
+
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let recorded = calls.clone();
+        lox.define_native("record", 1, move |arguments| {
+            recorded.borrow_mut().push(arguments[0].clone());
+            Ok(Literal::Number(recorded.borrow().len() as f64))
+        });
+
+        let diagnostics = lox.run(b"print record(\"a\"); print record(nil);".to_vec());
+        assert!(diagnostics.is_empty(), "{backend:?}");
+
+        // Natives defined on the session survive a reset.
+        lox.reset();
+        let diagnostics = lox.run(b"print record(true);".to_vec());
+        assert!(diagnostics.is_empty(), "{backend:?}");
+
+        assert_eq!(
+            calls.borrow().as_slice(),
+            [
+                Literal::String("a".to_string()),
+                Literal::Nil,
+                Literal::Bool(true)
+            ]
+        );
+        assert_eq!(output.borrow().as_slice(), b"1\n2\n3\n", "{backend:?}");
+    }
+}
+
+#[test]
+fn lox_natives_report_the_same_errors_on_both_backends() {
+    let mut reported = vec![];
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        let arenas = Arenas::new();
+        let (mut lox, _) = lox_with_output(&arenas, backend);
+
+        lox.define_native("fail", 1, |_| {
+            Err(RuntimeError::from_native("Something went wrong."))
+        });
+
+        let errors: Vec<_> = ["fail(1, 2);", "fail(1);", "class A {}\nfail(A);"]
+            .into_iter()
+            .flat_map(|source| lox.run(source.as_bytes().to_vec()))
+            .map(|diagnostic| {
+                (
+                    diagnostic.code,
+                    diagnostic.message,
+                    diagnostic.notes,
+                    diagnostic.span,
+                )
+            })
+            .collect();
+
+        reported.push(errors);
+    }
+
+    assert_eq!(reported[0], reported[1]);
+
+    let lines: Vec<_> = reported[0]
+        .iter()
+        .map(|(code, message, notes, span)| (*code, message.as_str(), notes.clone(), span.line))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (
+                ErrorCode::WrongArity,
+                "Expected 1 arguments but got 2.",
+                vec![],
+                1
+            ),
+            (
+                ErrorCode::NativeFunctionFailed,
+                "Something went wrong.",
+                vec![],
+                2
+            ),
+            (
+                ErrorCode::NativeFunctionFailed,
+                "Native functions only take numbers, strings, booleans and nil.",
+                vec!["the argument is a class".to_string()],
+                4
+            ),
+        ]
+    );
+}
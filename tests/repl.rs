@@ -0,0 +1,76 @@
+// Exercises the library side of the REPL, which evaluates what is typed at
+// the prompt in a long-lived session.
+
+use rusty_lox::{Arenas, Lox, Value};
+
+#[test]
+fn returns_the_value_of_a_lone_expression() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let (diagnostics, value) = lox.run_repl(b"1 + 2".to_vec());
+    assert!(diagnostics.is_empty());
+    assert_eq!(value.as_deref(), Some("3"));
+
+    let (_, value) = lox.run_repl(b"var greeting = \"hi\";".to_vec());
+    assert_eq!(value, None);
+
+    let (_, value) = lox.run_repl(b"greeting + \"!\";".to_vec());
+    assert_eq!(value.as_deref(), Some("hi!"));
+}
+
+#[test]
+fn only_lone_expressions_are_shown() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    let (diagnostics, value) = lox.run_repl(b"var a = 1; a".to_vec());
+    assert!(diagnostics.is_empty());
+    assert_eq!(value, None);
+}
+
+#[test]
+fn scripts_still_require_semicolons() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    assert!(lox.run(b"1 + 2".to_vec()).had_error());
+    assert!(lox.run_repl(b"1 + 2 3".to_vec()).0.had_error());
+}
+
+#[test]
+fn lists_and_resets_bindings() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    lox.run(b"var b = 2; var a = \"one\";".to_vec());
+
+    let names: Vec<String> = lox
+        .interpreter()
+        .bindings()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, ["a", "b", "clock"]);
+
+    lox.reset();
+
+    assert_eq!(lox.interpreter().bindings().len(), 1);
+    assert!(lox.run(b"print a;".to_vec()).had_runtime_error());
+}
+
+#[test]
+fn lists_the_properties_of_instances() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas);
+
+    lox.run(b"class A { base() {} } class B < A { init() { this.field = 1; } } var b = B();".to_vec());
+
+    let bindings = lox.interpreter().bindings();
+    let (_, value) = bindings.iter().find(|(name, _)| name == "b").unwrap();
+    let Value::Instance(instance) = value else {
+        panic!("expected an instance");
+    };
+
+    assert_eq!(instance.borrow().property_names(), ["base", "field", "init"]);
+}
@@ -0,0 +1,154 @@
+// Checks that the bytecode VM behaves like the tree-walk interpreter,
+// beyond the output the Lox test suite compares.
+
+use std::{cell::RefCell, rc::Rc, thread};
+
+use rusty_lox::{Arenas, Backend, Diagnostic, ErrorCode, Lox, Phase};
+
+fn diagnostics(backend: Backend, source: &str) -> Vec<Diagnostic> {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas).with_backend(backend);
+
+    lox.run(source.as_bytes().to_vec()).into_iter().collect()
+}
+
+#[test]
+fn runtime_errors_match_the_interpreter() {
+    let programs = [
+        "print -\"a\";",
+        "print 1 + nil;",
+        "print \"a\" < 1;",
+        "print undefined;",
+        "undefined = 1;",
+        "var a = 1; a();",
+        "fun f(a) {} f(1, 2);",
+        "class A {} A(1);",
+        "class A {} print A().missing;",
+        "class A {} A().missing();",
+        "print 1.field;",
+        "1.field();",
+        "1.field = 2;",
+        "var NotClass = 1; class A < NotClass {}",
+        "class A {} class B < A { m() { super.missing(); } } B().m();",
+        "fun a() { b(); }\nfun b() {\n  nil + 1;\n}\na();",
+        "class A { init() { this.f(); } f() { -nil; } }\nA();",
+        "fun f() { f(); } f();",
+    ];
+
+    for program in programs {
+        let tree = diagnostics(Backend::Tree, program);
+        let vm = diagnostics(Backend::Vm, program);
+
+        assert_eq!(tree.len(), 1, "{program}");
+        assert_eq!(tree, vm, "{program}");
+    }
+}
+
+#[test]
+fn closures_share_captured_variables() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas).with_backend(Backend::Vm);
+
+    let program = "
+        var get; var set;
+        {
+            var shared = 1;
+            fun g() { return shared; }
+            fun s(value) { shared = value; }
+            get = g; set = s;
+        }
+        set(2);";
+    assert!(lox.run(program.as_bytes().to_vec()).is_empty());
+
+    let (_, value) = lox.run_repl(b"get()".to_vec());
+    assert_eq!(value.as_deref(), Some("2"));
+}
+
+#[test]
+fn prints_to_the_output_the_host_sets() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let output = Rc::new(RefCell::new(Vec::new()));
+
+        let arenas = Arenas::new();
+        let mut lox = Lox::new(&arenas)
+            .with_backend(backend)
+            .with_output(output.clone());
+
+        lox.run(b"print 1 + 2; print \"a\" + \"b\";".to_vec());
+        lox.reset();
+        lox.run(b"print nil;".to_vec());
+
+        assert_eq!(output.borrow().as_slice(), b"3\nab\nnil\n", "{backend:?}");
+    }
+}
+
+#[test]
+fn globals_persist_between_runs() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas).with_backend(Backend::Vm);
+
+    lox.run(b"var a = 1; fun add(b) { return a + b; }".to_vec());
+
+    let (diagnostics, value) = lox.run_repl(b"add(2)".to_vec());
+    assert!(diagnostics.is_empty());
+    assert_eq!(value.as_deref(), Some("3"));
+
+    let names: Vec<String> = lox
+        .vm()
+        .globals()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, ["a", "add", "clock"]);
+
+    lox.reset();
+    assert!(lox.run(b"print a;".to_vec()).had_runtime_error());
+}
+
+#[test]
+fn reports_stack_overflow() {
+    let diagnostics = diagnostics(Backend::Vm, "fun f() { f(); } f();");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, ErrorCode::StackOverflow);
+    assert_eq!(diagnostics[0].trace.len(), 1024);
+}
+
+#[test]
+fn reaches_the_call_limit_on_a_small_stack() {
+    // Well below the 2 MiB threads get by default, which deep recursion in
+    // the tree-walk interpreter would overflow without growing the stack.
+    let handle = thread::Builder::new()
+        .stack_size(512 * 1024)
+        .spawn(|| diagnostics(Backend::Tree, "fun f(n) { if (n > 0) f(n - 1); } f(2000);"))
+        .unwrap();
+
+    let diagnostics = handle.join().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, ErrorCode::StackOverflow);
+}
+
+#[test]
+fn reports_too_many_locals() {
+    let locals: String = (0..300).map(|index| format!("var v{index};")).collect();
+    let program = format!("{{ {locals} }}");
+
+    let diagnostics = diagnostics(Backend::Vm, &program);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, ErrorCode::TooManyLocals);
+    assert_eq!(diagnostics[0].phase, Phase::Compile);
+
+    // The tree-walk interpreter has no such limit.
+    assert!(self::diagnostics(Backend::Tree, &program).is_empty());
+}
+
+#[test]
+fn collects_cycles() {
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas).with_backend(Backend::Vm);
+
+    lox.run(b"class A { m() {} } { var a = A(); a.m = a.m; }".to_vec());
+    lox.vm().collect_garbage();
+
+    assert!(lox.vm().gc_stats().objects_collected > 0);
+}
@@ -0,0 +1,118 @@
+// Checks the VM's `--disassemble` and `--trace-exec` output.
+
+use std::{cell::RefCell, env, fs, process::Command, rc::Rc};
+
+use rusty_lox::{Arenas, Backend, DebugConfig, Lox};
+
+fn run(name: &str, source: &str, args: &[&str]) -> (String, String, i32) {
+    let path = env::temp_dir().join(format!("rusty_lox_{name}.lox"));
+    fs::write(&path, source).expect("failed to write script");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rusty-lox"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .expect("failed to run rusty-lox");
+
+    let _ = fs::remove_file(&path);
+
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code().unwrap_or(-1),
+    )
+}
+
+#[test]
+fn disassembles_every_function() {
+    let source = "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2);\n";
+    let (stdout, stderr, code) = run("disassemble", source, &["--backend=vm", "--disassemble"]);
+
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "3\n");
+    assert_eq!(
+        stderr,
+        "\
+== <script> ==
+0000    1 OP_CLOSURE          0 <fn add>
+0003    | OP_DEFINE_GLOBAL    1 'add'
+0006    4 OP_GET_GLOBAL       1 'add'
+0009    | OP_CONSTANT         2 '1'
+0012    | OP_CONSTANT         3 '2'
+0015    | OP_CALL             2
+0017    | OP_PRINT
+0018    | OP_NIL
+0019    | OP_RETURN
+
+== add ==
+0000    2 OP_GET_LOCAL        1
+0002    | OP_GET_LOCAL        2
+0004    | OP_ADD
+0005    | OP_RETURN
+0006    | OP_NIL
+0007    | OP_RETURN
+"
+    );
+}
+
+#[test]
+fn traces_the_stack_before_each_instruction() {
+    let (stdout, stderr, code) = run(
+        "trace_exec",
+        "print -(1 + 2);",
+        &["--backend=vm", "--trace-exec"],
+    );
+
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "-3\n");
+
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(
+        lines[..8],
+        [
+            "          [ <script> ]",
+            "0000    1 OP_CONSTANT         0 '1'",
+            "          [ <script> ][ 1 ]",
+            "0003    | OP_CONSTANT         1 '2'",
+            "          [ <script> ][ 1 ][ 2 ]",
+            "0006    | OP_ADD",
+            "          [ <script> ][ 3 ]",
+            "0007    | OP_NEGATE",
+        ]
+    );
+}
+
+#[test]
+fn requires_the_vm_backend() {
+    let (_, stderr, code) = run("tree_trace", "print 1;", &["--trace-exec"]);
+
+    assert_eq!(code, 64);
+    assert!(stderr.contains("need --backend=vm"));
+}
+
+#[test]
+fn writes_to_the_log_the_host_sets() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let arenas = Arenas::new();
+    let mut lox = Lox::new(&arenas)
+        .with_backend(Backend::Vm)
+        .with_debug(DebugConfig {
+            disassemble: true,
+            trace_exec: true,
+        })
+        .with_log(log.clone());
+
+    let diagnostics = lox.run(b"1;".to_vec());
+    assert!(diagnostics.is_empty());
+
+    let log = String::from_utf8(log.borrow().clone()).unwrap();
+    assert!(
+        log.starts_with("== <script> ==\n0000    1 OP_CONSTANT"),
+        "{log}"
+    );
+    assert!(
+        log.contains("          [ <script> ][ 1 ]\n0003    | OP_POP\n"),
+        "{log}"
+    );
+}